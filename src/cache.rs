@@ -0,0 +1,439 @@
+//! The cache of known repos.
+//!
+//! The cache is a versioned JSON file that lists every known repo along with
+//! what git-global last learned about it: when it was first discovered,
+//! whether a scan found it or it was added by hand, what kind of repo it is,
+//! and the URLs of its remotes. It also remembers which repos were forgotten,
+//! so that scans don't bring them back, when the last scan happened, and how
+//! repos are tagged.
+//!
+//! Older versions of git-global kept a plain list of paths in `repos.txt`,
+//! which is migrated to the new format the first time the cache is loaded.
+//!
+//! A cache that exists but can't be used, because it's corrupt or another
+//! version of git-global wrote it, is never overwritten, so that nothing it
+//! records (like repos added by hand or tags) is lost. Updates fail until it's
+//! moved aside, while reads treat it like a missing cache.
+//!
+//! Several git-global processes may use the cache at once, so it's always
+//! written to a temporary file that then replaces it, and readers never see a
+//! partly written cache. Updates also take an advisory lock on a `.lock` file
+//! next to the cache, via `CacheLock`, so that two processes can't both read
+//! the cache, change it and write it back, losing one of the changes.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions, create_dir_all};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tempfile::NamedTempFile;
+
+use crate::repo::{Repo, RepoKind};
+
+/// The version of the cache format written by this version of git-global.
+pub const VERSION: u32 = 1;
+
+/// The plain list of repo paths used by older versions of git-global.
+const LEGACY_CACHE_FILE: &str = "repos.txt";
+
+/// How a repo came to be known.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    /// A scan found it.
+    Scan,
+    /// It was registered with `git global add`.
+    Manual,
+}
+
+/// What the cache knows about a single repo.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CacheEntry {
+    pub path: PathBuf,
+    /// When the repo was first discovered, in seconds since the Unix epoch.
+    pub discovered: u64,
+    pub source: Source,
+    pub kind: RepoKind,
+    /// The URLs of the repo's remotes, by remote name, as of the last time it
+    /// was scanned or added.
+    pub remotes: BTreeMap<String, String>,
+}
+
+impl CacheEntry {
+    /// Creates an entry for a repo that was just discovered, reading its
+    /// remotes.
+    pub fn new(repo: &Repo, source: Source) -> CacheEntry {
+        let path = PathBuf::from(repo.path());
+        CacheEntry {
+            remotes: read_remotes(&path),
+            path,
+            discovered: now(),
+            source,
+            kind: repo.kind(),
+        }
+    }
+
+    /// Returns the `Repo` for this entry.
+    pub fn to_repo(&self) -> Repo {
+        Repo::with_kind(&self.path, self.kind)
+    }
+}
+
+/// The contents of the cache file.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Cache {
+    version: u32,
+    /// When the cache was last populated by a scan, in seconds since the Unix
+    /// epoch, or `None` if it never has been.
+    pub scanned: Option<u64>,
+    /// Known repos, sorted by path.
+    pub repos: Vec<CacheEntry>,
+    /// Repos that scans should leave out.
+    pub forgotten: Vec<PathBuf>,
+    /// The paths of the repos with each tag, by tag. Tags outlive the repos'
+    /// entries, so they survive a repo dropping out of a scan and coming
+    /// back.
+    #[serde(default)]
+    pub tags: BTreeMap<String, BTreeSet<PathBuf>>,
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Cache {
+            version: VERSION,
+            scanned: None,
+            repos: vec![],
+            forgotten: vec![],
+            tags: BTreeMap::new(),
+        }
+    }
+}
+
+impl Cache {
+    /// Reads the cache from the given file, first migrating the legacy file
+    /// next to it if the file doesn't exist yet.
+    ///
+    /// Returns `None` if there is no cache, or if it can't be used (e.g.,
+    /// because a newer version of git-global wrote it).
+    pub fn load(file: &Path) -> Option<Cache> {
+        if !file.exists() {
+            if !file.with_file_name(LEGACY_CACHE_FILE).exists() {
+                return None;
+            }
+            // Migrating writes the cache, which takes exclusive access.
+            return CacheLock::new(file).ok()?.load().ok()?;
+        }
+        // Writes are atomic, so the lock isn't strictly needed to read the
+        // cache, and a cache in a read-only directory is still readable
+        // without it. Reading never creates the lock file.
+        let _lock = lock_file(file, false).ok();
+        Cache::read(file).ok()?
+    }
+
+    /// Reads the given cache file, if it exists. Fails if it can't be read
+    /// or isn't of this version.
+    fn read(file: &Path) -> Result<Option<Cache>, String> {
+        let unusable = |reason: String| {
+            format!(
+                "The cache file {} can't be used, so it was left as it is \
+                 ({}). Move it aside to start a new one.",
+                file.display(),
+                reason
+            )
+        };
+        let f = match File::open(file) {
+            Ok(f) => f,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(unusable(e.to_string())),
+        };
+        let value: serde_json::Value =
+            serde_json::from_reader(BufReader::new(f))
+                .map_err(|e| unusable(e.to_string()))?;
+        let version = value.get("version").and_then(|v| v.as_u64());
+        if version != Some(u64::from(VERSION)) {
+            return Err(unusable(match version {
+                Some(version) => format!(
+                    "it's in version {} of the format, not version {}",
+                    version, VERSION
+                ),
+                None => "it has no version".to_string(),
+            }));
+        }
+        serde_json::from_value(value)
+            .map(Some)
+            .map_err(|e| unusable(e.to_string()))
+    }
+
+    /// Returns the entry for the repo at the given path, if any.
+    pub fn find(&self, path: &Path) -> Option<&CacheEntry> {
+        self.repos.iter().find(|entry| entry.path == path)
+    }
+
+    /// Returns the repos that the last scan found.
+    pub fn scanned_repos(&self) -> Vec<Repo> {
+        self.repos
+            .iter()
+            .filter(|entry| entry.source == Source::Scan)
+            .map(|entry| entry.to_repo())
+            .collect()
+    }
+
+    /// Sorts the entries by path.
+    pub fn sort(&mut self) {
+        self.repos.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+
+    /// Builds a cache from the legacy file next to the given cache file, if
+    /// there is one, then saves it and removes the legacy file.
+    fn migrate(lock: &CacheLock) -> Option<Cache> {
+        let cache_file = lock.file.with_file_name(LEGACY_CACHE_FILE);
+        if !cache_file.exists() {
+            return None;
+        }
+        // Without a record of when each repo was found, the legacy cache's
+        // last scan is the best guess.
+        let scanned = fs::metadata(&cache_file)
+            .and_then(|md| md.modified())
+            .ok()
+            .map(seconds_since_epoch);
+        let mut cache = Cache {
+            scanned,
+            ..Cache::default()
+        };
+        for path in read_paths(&cache_file) {
+            if cache.find(&path).is_none() {
+                let kind = RepoKind::detect(&path).unwrap_or(RepoKind::Normal);
+                let repo = Repo::with_kind(path, kind);
+                cache.repos.push(CacheEntry {
+                    discovered: scanned.unwrap_or_else(now),
+                    ..CacheEntry::new(&repo, Source::Scan)
+                });
+            }
+        }
+        cache.sort();
+        if lock.save(&cache).is_ok() {
+            let _ = fs::remove_file(cache_file);
+        }
+        Some(cache)
+    }
+}
+
+/// Exclusive access to the cache file, so that it can be read, changed and
+/// written back without another git-global process changing it in between.
+/// Other processes wait for the lock until it's dropped.
+pub struct CacheLock {
+    file: PathBuf,
+    _lock: File,
+}
+
+impl CacheLock {
+    /// Waits for exclusive access to the given cache file, creating its
+    /// directory if necessary.
+    pub fn new(file: &Path) -> io::Result<CacheLock> {
+        if let Some(parent) = file.parent() {
+            create_dir_all(parent)?;
+        }
+        Ok(CacheLock {
+            file: file.to_path_buf(),
+            _lock: lock_file(file, true)?,
+        })
+    }
+
+    /// Reads the cache, like `Cache::load()`, but fails if the cache exists
+    /// and can't be used, rather than letting it be replaced.
+    pub fn load(&self) -> Result<Option<Cache>, String> {
+        if !self.file.exists() {
+            return Ok(Cache::migrate(self));
+        }
+        Cache::read(&self.file)
+    }
+
+    /// Replaces the cache with the given one, atomically.
+    pub fn save(&self, cache: &Cache) -> io::Result<()> {
+        let dir = match self.file.parent() {
+            Some(parent) if parent != Path::new("") => parent,
+            _ => Path::new("."),
+        };
+        let mut temp = NamedTempFile::new_in(dir)?;
+        {
+            let mut writer = BufWriter::new(temp.as_file_mut());
+            serde_json::to_writer_pretty(&mut writer, cache)?;
+            writer.flush()?;
+        }
+        temp.as_file().sync_all()?;
+        temp.persist(&self.file)?;
+        Ok(())
+    }
+}
+
+/// Opens the lock file for the given cache file and waits for a lock on it,
+/// either exclusive or shared. The lock is released when the file is closed.
+/// Only an exclusive lock creates the lock file, so that just reading the
+/// cache never writes anything.
+fn lock_file(file: &Path, exclusive: bool) -> io::Result<File> {
+    let mut name = OsString::from(file.as_os_str());
+    name.push(".lock");
+    let f = OpenOptions::new()
+        .create(exclusive)
+        .truncate(false)
+        .read(true)
+        .write(exclusive)
+        .open(PathBuf::from(name))?;
+    if exclusive {
+        f.lock()?;
+    } else {
+        f.lock_shared()?;
+    }
+    Ok(f)
+}
+
+/// Returns the current time, in seconds since the Unix epoch.
+pub fn now() -> u64 {
+    seconds_since_epoch(SystemTime::now())
+}
+
+/// Converts a time to seconds since the Unix epoch.
+fn seconds_since_epoch(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Returns the URLs of a repo's remotes, by remote name.
+fn read_remotes(path: &Path) -> BTreeMap<String, String> {
+    let Ok(repo) = git2::Repository::open(path) else {
+        return BTreeMap::new();
+    };
+    let Ok(names) = repo.remotes() else {
+        return BTreeMap::new();
+    };
+    names
+        .iter()
+        .filter_map(|name| {
+            let name = name.ok()??;
+            let remote = repo.find_remote(name).ok()?;
+            Some((name.to_string(), remote.url().ok()?.to_string()))
+        })
+        .collect()
+}
+
+/// Returns the paths listed one per line in a file, or none if the file
+/// doesn't exist.
+fn read_paths(file: &Path) -> Vec<PathBuf> {
+    match File::open(file) {
+        Ok(f) => BufReader::new(f)
+            .lines()
+            .map_while(Result::ok)
+            .map(PathBuf::from)
+            .collect(),
+        Err(_) => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::write;
+
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_legacy_file_is_migrated() {
+        let tempdir = TempDir::new().unwrap();
+        let dir = tempdir.path();
+        let repo_path = dir.join("repo");
+        let repo = git2::Repository::init(&repo_path).unwrap();
+        repo.remote("origin", "https://example.com/repo.git")
+            .unwrap();
+        write(
+            dir.join(LEGACY_CACHE_FILE),
+            format!("{}\n/gone\n", repo_path.display()),
+        )
+        .unwrap();
+
+        let file = dir.join("repos.json");
+        let cache = Cache::load(&file).unwrap();
+        assert!(cache.scanned.is_some());
+        assert_eq!(cache.repos.len(), 2);
+        assert_eq!(cache.repos[0].path, PathBuf::from("/gone"));
+        let entry = cache.find(&repo_path).unwrap();
+        assert_eq!(entry.source, Source::Scan);
+        assert_eq!(entry.kind, RepoKind::Normal);
+        assert_eq!(entry.remotes["origin"], "https://example.com/repo.git");
+
+        // The legacy file is replaced by the new cache.
+        assert!(!dir.join(LEGACY_CACHE_FILE).exists());
+        assert_eq!(Cache::load(&file).unwrap().repos.len(), 2);
+    }
+
+    #[test]
+    fn test_reading_writes_nothing() {
+        let tempdir = TempDir::new().unwrap();
+        let dir = tempdir.path().join("cache");
+        let file = dir.join("repos.json");
+        assert!(Cache::load(&file).is_none());
+        assert!(!dir.exists());
+
+        create_dir_all(&dir).unwrap();
+        CacheLock::new(&file)
+            .unwrap()
+            .save(&Cache::default())
+            .unwrap();
+        fs::remove_file(dir.join("repos.json.lock")).unwrap();
+        assert!(Cache::load(&file).is_some());
+        assert!(!dir.join("repos.json.lock").exists());
+    }
+
+    #[test]
+    fn test_newer_versions_are_not_read() {
+        let tempdir = TempDir::new().unwrap();
+        let file = tempdir.path().join("repos.json");
+        let cache = Cache {
+            version: VERSION + 1,
+            ..Cache::default()
+        };
+        CacheLock::new(&file).unwrap().save(&cache).unwrap();
+        assert!(Cache::load(&file).is_none());
+        // Nor can they be replaced.
+        let error = CacheLock::new(&file).unwrap().load().err().unwrap();
+        assert!(error.contains("version 2 of the format, not version 1"));
+    }
+
+    #[test]
+    fn test_corrupt_caches_are_not_replaced() {
+        let tempdir = TempDir::new().unwrap();
+        let file = tempdir.path().join("repos.json");
+        write(&file, "{\"version\": 1, \"repos\": [").unwrap();
+        assert!(Cache::load(&file).is_none());
+        assert!(CacheLock::new(&file).unwrap().load().is_err());
+    }
+
+    #[test]
+    fn test_concurrent_updates_are_not_lost() {
+        let tempdir = TempDir::new().unwrap();
+        let file = tempdir.path().join("repos.json");
+        CacheLock::new(&file)
+            .unwrap()
+            .save(&Cache::default())
+            .unwrap();
+        std::thread::scope(|scope| {
+            for i in 0..8 {
+                let file = &file;
+                scope.spawn(move || {
+                    let lock = CacheLock::new(file).unwrap();
+                    let mut cache = lock.load().unwrap().unwrap();
+                    cache.forgotten.push(PathBuf::from(format!("/{}", i)));
+                    lock.save(&cache).unwrap();
+                });
+            }
+            // Readers never see a partly written cache.
+            for _ in 0..100 {
+                assert!(Cache::load(&file).is_some());
+            }
+        });
+        assert_eq!(Cache::load(&file).unwrap().forgotten.len(), 8);
+    }
+}
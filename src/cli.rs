@@ -2,10 +2,11 @@
 
 use std::io::{Write, stderr, stdout};
 
-use clap::{Arg, ArgAction, ArgMatches, Command, command};
+use clap::{Arg, ArgAction, ArgMatches, Command, command, value_parser};
 use serde_json::json;
 
 use crate::config::Config;
+use crate::query::Query;
 use crate::subcommands;
 
 /// Returns the definitive clap::Command instance for git-global.
@@ -27,6 +28,14 @@ pub fn get_clap_app() -> Command {
                 .global(true)
                 .help("Enable verbose mode."),
         )
+        .arg(
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .action(ArgAction::SetTrue)
+                .global(true)
+                .help("Don't print progress to stderr."),
+        )
         .arg(
             Arg::new("json")
                 .short('j')
@@ -53,6 +62,51 @@ pub fn get_clap_app() -> Command {
                 .global(true)
                 .help("Don't show untracked files in output."),
         )
+        .arg(
+            Arg::new("filter")
+                .short('f')
+                .long("filter")
+                .value_name("GLOB")
+                .action(ArgAction::Append)
+                .global(true)
+                .help("Only include repos matching GLOB; may be given more than once."),
+        )
+        .arg(
+            Arg::new("exclude")
+                .short('x')
+                .long("exclude")
+                .value_name("GLOB")
+                .action(ArgAction::Append)
+                .global(true)
+                .help("Leave out repos matching GLOB; may be given more than once."),
+        )
+        .arg(
+            Arg::new("here")
+                .short('H')
+                .long("here")
+                .action(ArgAction::SetTrue)
+                .global(true)
+                .help("Only include repos under the current directory."),
+        )
+        .arg(
+            Arg::new("where")
+                .short('w')
+                .long("where")
+                .value_name("EXPR")
+                .value_parser(Query::parse)
+                .global(true)
+                .help("Only include repos whose state satisfies EXPR, e.g. 'dirty && !has_remote'."),
+        )
+        .arg(
+            Arg::new("tag")
+                .short('g')
+                .long("tag")
+                .visible_alias("group")
+                .value_name("TAG")
+                .action(ArgAction::Append)
+                .global(true)
+                .help("Only include repos with TAG; may be given more than once."),
+        )
         .subcommands(
             subcommands::get_subcommands()
                 .iter()
@@ -61,19 +115,160 @@ pub fn get_clap_app() -> Command {
                     if *cmd == "ignore" {
                         subcmd = subcmd.arg(
                             Arg::new("pattern")
-                                .help("Pattern to add to global.ignore (matches paths containing this string)")
+                                .help("Pattern to add to global.ignore (a .gitignore-style glob, unless prefixed with `substring:`)")
                                 .required(true)
                                 .index(1),
                         );
                     }
-                    if *cmd == "scan" {
+                    if *cmd == "add" || *cmd == "forget" {
                         subcmd = subcmd.arg(
                             Arg::new("paths")
-                                .help("Additional directories to scan for git repos")
-                                .num_args(0..)
+                                .help("Paths to git repos")
+                                .required(true)
+                                .num_args(1..)
                                 .value_name("PATH"),
                         );
                     }
+                    if *cmd == "tag" || *cmd == "untag" {
+                        subcmd = subcmd
+                            .arg(
+                                Arg::new("name")
+                                    .help("The tag")
+                                    .required(*cmd == "untag")
+                                    .value_name("TAG")
+                                    .index(1),
+                            )
+                            .arg(
+                                Arg::new("targets")
+                                    .help("Paths to known repos, or glob patterns matching them")
+                                    .required(false)
+                                    .num_args(1..)
+                                    .value_name("PATH|PATTERN")
+                                    .index(2),
+                            );
+                        if *cmd == "tag" {
+                            subcmd = subcmd.mut_arg("name", |arg| {
+                                arg.requires("targets")
+                            });
+                        }
+                    }
+                    if *cmd == "exec" {
+                        subcmd = subcmd
+                            .arg(
+                                Arg::new("jobs")
+                                    .long("jobs")
+                                    .value_name("N")
+                                    .value_parser(value_parser!(u64).range(1..))
+                                    .help("Run the command in at most N repos at once [default: the number of CPUs]"),
+                            )
+                            .arg(
+                                Arg::new("fail_fast")
+                                    .long("fail-fast")
+                                    .action(ArgAction::SetTrue)
+                                    .help("Stop starting the command in more repos once it fails in one"),
+                            )
+                            .arg(
+                                Arg::new("only_failed")
+                                    .long("only-failed")
+                                    .action(ArgAction::SetTrue)
+                                    .help("Only show output from repos where the command failed"),
+                            )
+                            .arg(
+                                Arg::new("command")
+                                    .help("The command to run, and its arguments")
+                                    .required(true)
+                                    .num_args(1..)
+                                    .trailing_var_arg(true)
+                                    .allow_hyphen_values(true)
+                                    .value_name("COMMAND"),
+                            );
+                    }
+                    if *cmd == "git" {
+                        subcmd = subcmd.arg(
+                            Arg::new("args")
+                                .help("Arguments to pass to git")
+                                .required(true)
+                                .num_args(1..)
+                                .trailing_var_arg(true)
+                                .allow_hyphen_values(true)
+                                .value_name("GIT_ARGS"),
+                        );
+                    }
+                    if *cmd == "grep" {
+                        subcmd = subcmd
+                            .arg(
+                                Arg::new("ignore_case")
+                                    .short('i')
+                                    .long("ignore-case")
+                                    .action(ArgAction::SetTrue)
+                                    .help("Match the pattern regardless of case"),
+                            )
+                            .arg(
+                                Arg::new("cached")
+                                    .long("cached")
+                                    .action(ArgAction::SetTrue)
+                                    .conflicts_with("worktree")
+                                    .help("Search the files in the index, instead of those in HEAD"),
+                            )
+                            .arg(
+                                Arg::new("worktree")
+                                    .long("worktree")
+                                    .action(ArgAction::SetTrue)
+                                    .help("Search the working tree's copies of the files in the index, instead of those in HEAD"),
+                            )
+                            .arg(
+                                Arg::new("pattern")
+                                    .help("A regular expression to search for")
+                                    .required(true)
+                                    .value_name("PATTERN")
+                                    .index(1),
+                            )
+                            .arg(
+                                Arg::new("pathspecs")
+                                    .help("Only search files matching these pathspecs, relative to the top of each repo")
+                                    .num_args(1..)
+                                    .value_name("PATHSPEC")
+                                    .index(2),
+                            );
+                    }
+                    if *cmd == "fetch" {
+                        subcmd = subcmd.arg(
+                            Arg::new("prune")
+                                .short('p')
+                                .long("prune")
+                                .action(ArgAction::SetTrue)
+                                .help("Remove remote-tracking refs whose branches are gone from their remotes"),
+                        );
+                    }
+                    if *cmd == "prune" || *cmd == "pull" || *cmd == "push" {
+                        subcmd = subcmd.arg(
+                            Arg::new("dry_run")
+                                .short('n')
+                                .long("dry-run")
+                                .action(ArgAction::SetTrue)
+                                .help(match *cmd {
+                                    "prune" => "Only show what would be pruned",
+                                    "pull" => "Only show what would be fast-forwarded",
+                                    _ => "Only show what would be pushed",
+                                }),
+                        );
+                    }
+                    if *cmd == "scan" {
+                        subcmd = subcmd
+                            .arg(
+                                Arg::new("paths")
+                                    .help("Additional directories to scan for git repos")
+                                    .num_args(0..)
+                                    .value_name("PATH"),
+                            )
+                            .arg(
+                                Arg::new("incremental")
+                                    .short('i')
+                                    .long("incremental")
+                                    .action(ArgAction::SetTrue)
+                                    .help("Only re-read directories that changed since the last scan"),
+                            );
+                    }
                     subcmd
                 }),
         )
@@ -84,12 +279,33 @@ fn merge_args_with_config(config: &mut Config, matches: &ArgMatches) {
     if matches.get_flag("verbose") {
         config.verbose = true;
     }
+    if matches.get_flag("quiet") {
+        config.quiet = true;
+    }
+    if matches.get_flag("json") {
+        config.json = true;
+    }
     if matches.get_flag("untracked") {
         config.show_untracked = true;
     }
     if matches.get_flag("nountracked") {
         config.show_untracked = false;
     }
+    if let Some(tags) = matches.get_many::<String>("tag") {
+        config.tags = tags.cloned().collect();
+    }
+    if let Some(filters) = matches.get_many::<String>("filter") {
+        config.filters = filters.cloned().collect();
+    }
+    if let Some(excludes) = matches.get_many::<String>("exclude") {
+        config.excludes = excludes.cloned().collect();
+    }
+    if matches.get_flag("here") {
+        config.here = true;
+    }
+    if let Some(query) = matches.get_one::<Query>("where") {
+        config.query = Some(query.clone());
+    }
 }
 
 /// Runs the appropriate git-global subcommand based on command line arguments.
@@ -105,33 +321,19 @@ pub fn run_from_command_line() -> i32 {
     };
     merge_args_with_config(&mut config, &matches);
 
-    // Extract additional arguments for subcommands that need them
-    let args: Vec<String> = matches
+    // Pass along the subcommand's own arguments, if one was given.
+    let no_args = ArgMatches::default();
+    let args = matches
         .subcommand()
-        .map(|(name, sub_matches)| match name {
-            "ignore" => sub_matches
-                .get_one::<String>("pattern")
-                .cloned()
-                .into_iter()
-                .collect(),
-            "scan" => sub_matches
-                .get_many::<String>("paths")
-                .map(|v| v.cloned().collect())
-                .unwrap_or_default(),
-            _ => vec![],
-        })
-        .unwrap_or_default();
+        .map(|(_, sub_matches)| sub_matches)
+        .unwrap_or(&no_args);
 
     let report = subcommands::run(matches.subcommand_name(), config, args);
     let use_json = matches.get_flag("json");
     match report {
         Ok(rep) => {
-            if use_json {
-                rep.print_json(&mut stdout());
-            } else {
-                rep.print(&mut stdout());
-            }
-            0
+            rep.write(&mut stdout(), use_json);
+            if rep.is_failed() { 1 } else { 0 }
         }
         Err(err) => {
             if use_json {
@@ -1,45 +1,296 @@
 //! Configuration of git-global.
 //!
-//! Exports the `Config` struct, which defines the base path for finding git
+//! Exports the `Config` struct, which defines the base paths for finding git
 //! repos on the machine, path patterns to ignore when scanning for repos, the
 //! location of a cache file, and other config options for running git-global.
+//!
+//! `global.basedir` may be given more than once. Each base directory can have
+//! settings of its own in a `[global "<basedir>"]` section, where `<basedir>`
+//! is written exactly as in `global.basedir`:
+//!
+//! ```text
+//! [global]
+//!     basedir = ~/src
+//!     basedir = ~/work
+//! [global "~/work"]
+//!     ignore = vendor,third_party
+//!     max-depth = 2
+//! ```
 
+use std::collections::{BTreeMap, HashSet};
 use std::env;
-use std::fs::{File, create_dir_all, remove_file};
-use std::io::{BufRead, BufReader, Write};
-use std::path::{Path, PathBuf};
+use std::fmt;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
 
 use directories::{ProjectDirs, UserDirs};
-use walkdir::{DirEntry, WalkDir};
+use serde::Serialize;
 
-use crate::repo::Repo;
+use crate::cache::{self, Cache, CacheEntry, CacheLock, Source};
+use crate::parallel::{default_parallelism, run_parallel};
+use crate::pattern::{self, Glob, IgnorePattern, PatternStyle};
+use crate::progress::Progress;
+use crate::query::Query;
+use crate::repo::{Repo, RepoKind};
+use crate::snapshot::{Recorder, Snapshot};
+use crate::walk::{self, DirEntry, ReadDir, WalkState, Walker};
 
 const QUALIFIER: &str = "";
 const ORGANIZATION: &str = "peap";
 const APPLICATION: &str = "git-global";
-const CACHE_FILE: &str = "repos.txt";
+const CACHE_FILE: &str = "repos.json";
+const SNAPSHOT_FILE: &str = "dirs.json";
 
 const DEFAULT_CMD: &str = "status";
 const DEFAULT_FOLLOW_SYMLINKS: bool = true;
 const DEFAULT_SAME_FILESYSTEM: bool = cfg!(any(unix, windows));
+const DEFAULT_SCAN_NESTED: bool = false;
 const DEFAULT_VERBOSE: bool = false;
 const DEFAULT_SHOW_UNTRACKED: bool = true;
+const DEFAULT_IGNORE_STYLE: PatternStyle = PatternStyle::Substring;
 
 const SETTING_BASEDIR: &str = "global.basedir";
 const SETTING_FOLLOW_SYMLINKS: &str = "global.follow-symlinks";
 const SETTING_SAME_FILESYSTEM: &str = "global.same-filesystem";
+const SETTING_SCAN_NESTED: &str = "global.scan-nested";
 const SETTING_IGNORE: &str = "global.ignore";
+const SETTING_IGNORE_STYLE: &str = "global.ignore-style";
 const SETTING_DEFAULT_CMD: &str = "global.default-cmd";
 const SETTING_SHOW_UNTRACKED: &str = "global.show-untracked";
 const SETTING_VERBOSE: &str = "global.verbose";
+const SETTING_CACHE_TTL: &str = "global.cache-ttl";
+const BASEDIR_SETTING_IGNORE: &str = "ignore";
+const BASEDIR_SETTING_MAX_DEPTH: &str = "max-depth";
+
+/// How a scan treats directories it has already seen.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ScanMode {
+    /// Read every directory.
+    Full,
+    /// Only read directories that have changed since the last scan, using the
+    /// snapshot that scan left behind. Falls back to a full scan if there is
+    /// no snapshot.
+    Incremental,
+}
+
+/// The repos that updating the cache added and removed, and the cache as it
+/// was saved, if there is a cache file.
+type CacheUpdate = (Vec<Repo>, Vec<Repo>, Option<Cache>);
+
+/// The results of scanning for git repos.
+pub struct ScanResult {
+    /// All repos that were found.
+    pub repos: Vec<Repo>,
+    /// Repos that were found but weren't in the cache before the scan.
+    pub added: Vec<Repo>,
+    /// Repos that were in the cache before the scan but weren't found.
+    pub removed: Vec<Repo>,
+    /// The number of directories that were unchanged since the last scan, and
+    /// so weren't read again.
+    pub unchanged_dirs: usize,
+    /// Problems that kept parts of the filesystem from being scanned, sorted
+    /// by path.
+    pub errors: Vec<ScanError>,
+    /// Why the results couldn't be saved to the cache, if they couldn't be.
+    pub cache_error: Option<String>,
+    /// The cache as it was saved, if it was.
+    cache: Option<Cache>,
+}
+
+/// Why a repo was pruned from the cache.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PruneReason {
+    /// Nothing exists at the repo's path anymore.
+    Missing,
+    /// Something exists at the repo's path, but it isn't a git repo.
+    NotARepo,
+}
+
+impl fmt::Display for PruneReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PruneReason::Missing => write!(f, "missing"),
+            PruneReason::NotARepo => write!(f, "not a git repo"),
+        }
+    }
+}
+
+/// The kinds of problems that can keep a path from being scanned.
+#[derive(
+    Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScanErrorKind {
+    PermissionDenied,
+    BrokenSymlink,
+    SymlinkLoop,
+    NonUtf8Path,
+    Other,
+}
+
+impl fmt::Display for ScanErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ScanErrorKind::PermissionDenied => write!(f, "permission denied"),
+            ScanErrorKind::BrokenSymlink => write!(f, "broken symbolic link"),
+            ScanErrorKind::SymlinkLoop => write!(f, "symbolic link loop"),
+            ScanErrorKind::NonUtf8Path => write!(f, "non-UTF-8 path"),
+            ScanErrorKind::Other => write!(f, "other error"),
+        }
+    }
+}
+
+/// A problem that kept a path from being scanned.
+#[derive(Clone, Debug, Serialize)]
+pub struct ScanError {
+    /// The path, with any invalid UTF-8 replaced.
+    pub path: String,
+    pub kind: ScanErrorKind,
+    pub message: String,
+}
+
+impl ScanError {
+    /// Classifies an error from walking the filesystem.
+    fn from_walk(err: walk::Error) -> ScanError {
+        let kind = if err.is_loop() {
+            ScanErrorKind::SymlinkLoop
+        } else {
+            match err.io_error().map(|e| e.kind()) {
+                Some(io::ErrorKind::PermissionDenied) => {
+                    ScanErrorKind::PermissionDenied
+                }
+                Some(io::ErrorKind::NotFound)
+                    if err.path().is_symlink() && !err.path().exists() =>
+                {
+                    ScanErrorKind::BrokenSymlink
+                }
+                _ => ScanErrorKind::Other,
+            }
+        };
+        ScanError {
+            path: err.path().to_string_lossy().into_owned(),
+            kind,
+            message: err.to_string(),
+        }
+    }
+
+    /// Records a path that was skipped because it isn't valid UTF-8.
+    fn non_utf8(path: &Path) -> ScanError {
+        let path = path.to_string_lossy().into_owned();
+        ScanError {
+            message: format!("{}: path is not valid UTF-8", path),
+            path,
+            kind: ScanErrorKind::NonUtf8Path,
+        }
+    }
+}
+
+/// A directory to search for git repos, with settings of its own.
+#[derive(Clone, Debug)]
+pub struct BaseDir {
+    /// The directory to walk.
+    pub path: PathBuf,
+
+    /// Path patterns to ignore under this directory, in addition to (and
+    /// taking precedence over) `Config::ignored_patterns`.
+    ///
+    /// Default: none
+    pub ignored_patterns: Vec<IgnorePattern>,
+
+    /// How many directories below `path` a repo may be. Repos directly
+    /// inside `path` are at depth 1.
+    ///
+    /// Default: no limit
+    pub max_depth: Option<usize>,
+}
+
+impl BaseDir {
+    /// Create a new `BaseDir` with no settings of its own.
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        BaseDir {
+            path: path.into(),
+            ignored_patterns: vec![],
+            max_depth: None,
+        }
+    }
+
+    /// Reads the base directories from `global.basedir` in gitconfig, along
+    /// with the settings in each one's `[global "<basedir>"]` section.
+    fn read_all(
+        cfg: &git2::Config,
+        homedir: &Path,
+        ignore_style: PatternStyle,
+    ) -> Vec<BaseDir> {
+        let mut values = Vec::new();
+        if let Ok(mut entries) = cfg.multivar(SETTING_BASEDIR, None) {
+            while let Some(Ok(entry)) = entries.next() {
+                if let Ok(value) = entry.value()
+                    && !values.iter().any(|v| v == value)
+                {
+                    values.push(value.to_string());
+                }
+            }
+        }
+        values
+            .iter()
+            .map(|value| {
+                let setting = |name| format!("global.{}.{}", value, name);
+                let path = match value.strip_prefix("~/") {
+                    Some(rest) => homedir.join(rest),
+                    None if value == "~" => homedir.to_path_buf(),
+                    None => PathBuf::from(value),
+                };
+                BaseDir {
+                    path,
+                    ignored_patterns: parse_patterns(
+                        &cfg.get_string(&setting(BASEDIR_SETTING_IGNORE))
+                            .unwrap_or_default(),
+                        ignore_style,
+                    ),
+                    max_depth: cfg
+                        .get_i64(&setting(BASEDIR_SETTING_MAX_DEPTH))
+                        .ok()
+                        .and_then(|depth| usize::try_from(depth).ok()),
+                }
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for BaseDir {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut notes = Vec::new();
+        if let Some(depth) = self.max_depth {
+            notes.push(format!("max depth {}", depth));
+        }
+        if !self.ignored_patterns.is_empty() {
+            let patterns: Vec<String> = self
+                .ignored_patterns
+                .iter()
+                .map(|p| p.to_string())
+                .collect();
+            notes.push(format!("ignoring {}", patterns.join(", ")));
+        }
+        if notes.is_empty() {
+            write!(f, "{}", self.path.display())
+        } else {
+            write!(f, "{} ({})", self.path.display(), notes.join("; "))
+        }
+    }
+}
 
 /// A container for git-global configuration options.
 #[derive(Clone, Debug)]
 pub struct Config {
-    /// The base directory to walk when searching for git repositories.
+    /// The base directories to walk when searching for git repositories.
     ///
     /// Default: $HOME.
-    pub basedir: PathBuf,
+    pub basedirs: Vec<BaseDir>,
 
     /// Whether to follow symbolic links when searching for git repos.
     ///
@@ -52,16 +303,43 @@ pub struct Config {
     /// Default: true [on supported platforms]
     pub same_filesystem: bool,
 
-    /// Path patterns to ignore when searching for git repositories.
+    /// Whether to keep searching a repo's working tree for other repos nested
+    /// inside it, once it's been found. Registered submodules are never
+    /// reported as repos of their own.
+    ///
+    /// Default: false
+    pub scan_nested: bool,
+
+    /// Path patterns to ignore under every base directory when searching for
+    /// git repositories.
     ///
     /// Default: none
-    pub ignored_patterns: Vec<String>,
+    pub ignored_patterns: Vec<IgnorePattern>,
+
+    /// How patterns without a `glob:` or `substring:` prefix are matched.
+    /// Substring matching by default keeps patterns written for older
+    /// versions working as before.
+    ///
+    /// Default: `substring`
+    pub ignore_style: PatternStyle,
 
     /// The git-global subcommand to run when unspecified.
     ///
     /// Default: `status`
     pub default_cmd: String,
 
+    /// Whether to suppress progress output, which otherwise goes to stderr
+    /// when it's a terminal.
+    ///
+    /// Default: false
+    pub quiet: bool,
+
+    /// Whether results are written as JSON, for subcommands that write them
+    /// as they go, rather than in a single report.
+    ///
+    /// Default: false
+    pub json: bool,
+
     /// Whether to enable verbose mode.
     ///
     /// Default: false
@@ -74,10 +352,48 @@ pub struct Config {
 
     /// Optional path to a cache file for git-global's usage.
     ///
-    /// Default: `repos.txt` in the user's XDG cache directory, if we understand
+    /// Default: `repos.json` in the user's XDG cache directory, if we understand
     /// XDG for the host system.
     pub cache_file: Option<PathBuf>,
 
+    /// How long a scan's results stay fresh. Once the last scan is older than
+    /// this, the next command rescans (incrementally) before it runs.
+    ///
+    /// Default: none (the cache never expires)
+    pub cache_ttl: Option<Duration>,
+
+    /// Tags to limit `get_repos()` to: if any are given, only repos with at
+    /// least one of them are returned.
+    ///
+    /// Default: none (all repos are returned)
+    pub tags: Vec<String>,
+
+    /// Glob patterns to limit `get_repos()` to: if any are given, only repos
+    /// matching at least one of them are returned. A pattern with a `/` is
+    /// matched against a repo's full path, one without against the name of
+    /// its directory.
+    ///
+    /// Default: none (all repos are returned)
+    pub filters: Vec<String>,
+
+    /// Glob patterns, like those of `filters`, for repos to leave out of
+    /// `get_repos()`.
+    ///
+    /// Default: none
+    pub excludes: Vec<String>,
+
+    /// Whether to limit `get_repos()` to the repos in or under the current
+    /// directory, and the repo that contains it, if any.
+    ///
+    /// Default: false
+    pub here: bool,
+
+    /// An expression over the state of each repo (see the `query` module) to
+    /// limit `get_repos()` to.
+    ///
+    /// Default: none (all repos are returned)
+    pub query: Option<Query>,
+
     /// Optional path to our manpage, regardless of whether it's installed.
     ///
     /// Default: `git-global.1` in the relevant manpages directory, if we
@@ -142,45 +458,79 @@ impl Config {
             None => git2::Config::open_default().ok(),
         };
         match git_cfg {
-            Some(cfg) => Config {
-                basedir: cfg.get_path(SETTING_BASEDIR).unwrap_or(homedir),
-                follow_symlinks: cfg
-                    .get_bool(SETTING_FOLLOW_SYMLINKS)
-                    .unwrap_or(DEFAULT_FOLLOW_SYMLINKS),
-                same_filesystem: cfg
-                    .get_bool(SETTING_SAME_FILESYSTEM)
-                    .unwrap_or(DEFAULT_SAME_FILESYSTEM),
-                ignored_patterns: cfg
-                    .get_string(SETTING_IGNORE)
-                    .unwrap_or_default()
-                    .split(',')
-                    .map(|p| p.trim().to_string())
-                    .filter(|p| !p.is_empty())
-                    .collect(),
-                default_cmd: cfg
-                    .get_string(SETTING_DEFAULT_CMD)
-                    .unwrap_or_else(|_| String::from(DEFAULT_CMD)),
-                verbose: cfg
-                    .get_bool(SETTING_VERBOSE)
-                    .unwrap_or(DEFAULT_VERBOSE),
-                show_untracked: cfg
-                    .get_bool(SETTING_SHOW_UNTRACKED)
-                    .unwrap_or(DEFAULT_SHOW_UNTRACKED),
-                cache_file,
-                manpage_file,
-                git_config_path,
-            },
+            Some(cfg) => {
+                let ignore_style = cfg
+                    .get_string(SETTING_IGNORE_STYLE)
+                    .ok()
+                    .and_then(|style| PatternStyle::from_name(&style))
+                    .unwrap_or(DEFAULT_IGNORE_STYLE);
+                let mut basedirs =
+                    BaseDir::read_all(&cfg, &homedir, ignore_style);
+                if basedirs.is_empty() {
+                    basedirs.push(BaseDir::new(homedir));
+                }
+                Config {
+                    basedirs,
+                    follow_symlinks: cfg
+                        .get_bool(SETTING_FOLLOW_SYMLINKS)
+                        .unwrap_or(DEFAULT_FOLLOW_SYMLINKS),
+                    same_filesystem: cfg
+                        .get_bool(SETTING_SAME_FILESYSTEM)
+                        .unwrap_or(DEFAULT_SAME_FILESYSTEM),
+                    scan_nested: cfg
+                        .get_bool(SETTING_SCAN_NESTED)
+                        .unwrap_or(DEFAULT_SCAN_NESTED),
+                    ignored_patterns: parse_patterns(
+                        &cfg.get_string(SETTING_IGNORE).unwrap_or_default(),
+                        ignore_style,
+                    ),
+                    ignore_style,
+                    default_cmd: cfg
+                        .get_string(SETTING_DEFAULT_CMD)
+                        .unwrap_or_else(|_| String::from(DEFAULT_CMD)),
+                    quiet: false,
+                    json: false,
+                    verbose: cfg
+                        .get_bool(SETTING_VERBOSE)
+                        .unwrap_or(DEFAULT_VERBOSE),
+                    show_untracked: cfg
+                        .get_bool(SETTING_SHOW_UNTRACKED)
+                        .unwrap_or(DEFAULT_SHOW_UNTRACKED),
+                    cache_file,
+                    cache_ttl: cfg
+                        .get_string(SETTING_CACHE_TTL)
+                        .ok()
+                        .and_then(|ttl| parse_duration(&ttl)),
+                    tags: vec![],
+                    filters: vec![],
+                    excludes: vec![],
+                    here: false,
+                    query: None,
+                    manpage_file,
+                    git_config_path,
+                }
+            }
             None => {
                 // Build the default configuration.
                 Config {
-                    basedir: homedir,
+                    basedirs: vec![BaseDir::new(homedir)],
                     follow_symlinks: DEFAULT_FOLLOW_SYMLINKS,
                     same_filesystem: DEFAULT_SAME_FILESYSTEM,
+                    scan_nested: DEFAULT_SCAN_NESTED,
                     ignored_patterns: vec![],
+                    ignore_style: DEFAULT_IGNORE_STYLE,
                     default_cmd: String::from(DEFAULT_CMD),
+                    quiet: false,
+                    json: false,
                     verbose: DEFAULT_VERBOSE,
                     show_untracked: DEFAULT_SHOW_UNTRACKED,
                     cache_file,
+                    cache_ttl: None,
+                    tags: vec![],
+                    filters: vec![],
+                    excludes: vec![],
+                    here: false,
+                    query: None,
                     manpage_file,
                     git_config_path,
                 }
@@ -188,170 +538,706 @@ impl Config {
         }
     }
 
-    /// Returns all known git repos, populating the cache first, if necessary.
+    /// Returns all known git repos, populating the cache first, if necessary,
+    /// or refreshing it if it's stale.
     pub fn get_repos(&mut self) -> Vec<Repo> {
-        if !self.has_cache() {
-            let repos = self.find_repos(&[]);
-            self.cache_repos(&repos);
+        let cache = self.refresh_cache();
+        self.select_repos(&cache)
+    }
+
+    /// Returns the cache, populating it first, if necessary, or refreshing
+    /// it if it's stale. No repos are selected, so this is cheap when the
+    /// cache is fresh. If a scan's results couldn't be saved, they're
+    /// returned anyway.
+    pub(crate) fn refresh_cache(&mut self) -> Cache {
+        let cache = self.load_cache();
+        let mode = match cache.as_ref().and_then(|cache| cache.scanned) {
+            // Without a cache, every invocation has to scan for itself.
+            None => ScanMode::Full,
+            Some(scanned) if self.is_stale(scanned) => ScanMode::Incremental,
+            Some(_) => return cache.unwrap_or_default(),
+        };
+        let result = self.scan(&[], mode);
+        if let Some(e) = &result.cache_error {
+            Progress::new(self.quiet)
+                .warn(format!("Could not save the list of repos: {}", e));
+        } else if let Some(cache) = result.cache {
+            return cache;
         }
-        self.get_cached_repos()
+        // Use what the scan found, since it couldn't be cached.
+        let mut cache = Cache::default();
+        cache.repos = result
+            .repos
+            .iter()
+            .map(|repo| CacheEntry::new(repo, Source::Scan))
+            .collect();
+        cache
+    }
+
+    /// Returns how long ago the cache was populated by a scan, or `None` if
+    /// it never has been.
+    pub fn cache_age(&self) -> Option<Duration> {
+        let scanned = self.load_cache()?.scanned?;
+        Some(age(scanned))
+    }
+
+    /// Returns `true` if the last scan is older than `cache_ttl`.
+    pub fn cache_is_stale(&self) -> bool {
+        self.load_cache()
+            .and_then(|cache| cache.scanned)
+            .is_some_and(|scanned| self.is_stale(scanned))
+    }
+
+    /// Returns `true` if a scan at the given time is older than `cache_ttl`.
+    fn is_stale(&self, scanned: u64) -> bool {
+        self.cache_ttl.is_some_and(|ttl| age(scanned) > ttl)
     }
 
-    /// Clears the cache, scans basedir and any extra paths, caches the
-    /// results, and returns the combined list of repos.
-    pub fn scan_with_extra_paths(
+    /// Scans the base directories and any extra paths, replaces the cache
+    /// with the results, and reports which repos were added or removed.
+    pub fn scan(
         &mut self,
         extra_paths: &[PathBuf],
-    ) -> Vec<Repo> {
-        self.clear_cache();
-        let repos = self.find_repos(extra_paths);
-        self.cache_repos(&repos);
-        repos
+        mode: ScanMode,
+    ) -> ScanResult {
+        let snapshot_file = self.snapshot_file();
+        let snapshot = match (mode, &snapshot_file) {
+            (ScanMode::Incremental, Some(file)) => Snapshot::load(file),
+            _ => None,
+        };
+        let recorder = Recorder::new(snapshot.as_ref());
+        let (mut repos, errors) = self.find_repos(&recorder, extra_paths);
+        let unchanged_dirs = recorder.n_reused();
+        let (added, removed, cache, cache_error) =
+            match self.update_cache(None, &mut repos) {
+                Ok((added, removed, cache)) => (added, removed, cache, None),
+                Err(e) => (vec![], vec![], None, Some(e)),
+            };
+        if let Some(file) = snapshot_file {
+            // The snapshot only speeds up the next incremental scan, so it's
+            // fine if it can't be written.
+            let _ = recorder.into_snapshot().save(&file);
+        }
+        ScanResult {
+            repos,
+            added,
+            removed,
+            unchanged_dirs,
+            errors,
+            cache_error,
+            cache,
+        }
     }
 
-    /// Clears the cache of known git repos, forcing a re-scan on the next
-    /// `get_repos()` call.
-    pub fn clear_cache(&mut self) {
-        if self.has_cache()
-            && let Some(file) = &self.cache_file
-        {
-            remove_file(file).expect("Failed to delete cache file.");
+    /// Rescans a single directory under one of the base directories,
+    /// updating the repos found under it in the cache, and reports which
+    /// repos were added or removed.
+    ///
+    /// The directory is scanned just as it would be by a full scan: the base
+    /// directory's ignore patterns and maximum depth apply, and nothing is
+    /// scanned if the directory is inside a repo (unless `scan_nested` is
+    /// set) or, with `same_filesystem`, on another filesystem. If the
+    /// directory no longer exists, the repos under it are removed.
+    pub(crate) fn scan_subtree<R: ReadDir>(
+        &self,
+        read_dir: &R,
+        root: &Path,
+    ) -> ScanResult {
+        let mut result = ScanResult {
+            repos: vec![],
+            added: vec![],
+            removed: vec![],
+            unchanged_dirs: 0,
+            errors: vec![],
+            cache_error: None,
+            cache: None,
+        };
+        let Some(basedir) = self
+            .basedirs
+            .iter()
+            .filter(|basedir| root.starts_with(&basedir.path))
+            .max_by_key(|basedir| basedir.path.components().count())
+        else {
+            return result;
+        };
+        // A directory inside a repo's working tree is only scanned in
+        // nested mode, and then only along with the whole repo, so that
+        // the repo's submodules are recognized as such.
+        let mut root = root.to_path_buf();
+        let enclosing = self
+            .load_cache()
+            .map(|cache| cache.repos)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|entry| entry.path)
+            .filter(|path| root.starts_with(path) && root != *path)
+            .min_by_key(|path| path.components().count());
+        if let Some(repo_path) = enclosing {
+            if !self.scan_nested {
+                return result;
+            }
+            root = repo_path;
         }
+        if root.exists() {
+            if self.same_filesystem
+                && walk::device_num(&root).ok()
+                    != walk::device_num(&basedir.path).ok()
+            {
+                return result;
+            }
+            self.scan_root(
+                read_dir,
+                basedir,
+                &root,
+                &mut result.repos,
+                &mut result.errors,
+            );
+        }
+        match self.update_cache(Some(&root), &mut result.repos) {
+            Ok((added, removed, cache)) => {
+                result.added = added;
+                result.removed = removed;
+                result.cache = cache;
+            }
+            Err(e) => result.cache_error = Some(e),
+        }
+        result
     }
 
-    /// Returns `true` if this directory entry should be included in scans.
-    fn filter(&self, entry: &DirEntry) -> bool {
-        if let Some(entry_path) = entry.path().to_str() {
-            self.ignored_patterns
+    /// Records that the cache is current as of now, without scanning, for
+    /// when something else, like `git global watch`, keeps it up to date.
+    pub(crate) fn mark_cache_fresh(&self) -> Result<(), String> {
+        let lock = self.lock_cache()?;
+        let Some(mut cache) = lock.load()? else {
+            return Ok(());
+        };
+        cache.scanned = Some(cache::now());
+        save_cache(&lock, &cache)
+    }
+
+    /// Replaces the repos that scans found under `scope` (or anywhere, if
+    /// it's `None`) in the cache with `repos`, less any forgotten ones, and
+    /// returns the repos that were added and removed, along with the cache as
+    /// it was saved.
+    ///
+    /// Repos added by hand are kept, as is when each known repo was first
+    /// discovered. Only a scan of everything counts as the cache's last scan.
+    /// Without a cache file, there's nothing to update, and nothing is added
+    /// or removed.
+    fn update_cache(
+        &self,
+        scope: Option<&Path>,
+        repos: &mut Vec<Repo>,
+    ) -> Result<CacheUpdate, String> {
+        if self.cache_file.is_none() {
+            return Ok((vec![], vec![], None));
+        }
+        let lock = self.lock_cache()?;
+        let previous = lock.load()?;
+        let in_scope =
+            |path: &Path| scope.is_none_or(|scope| path.starts_with(scope));
+        let mut cache = Cache::default();
+        if let Some(previous) = &previous {
+            repos.retain(|repo| {
+                !previous.forgotten.contains(&PathBuf::from(repo.path()))
+            });
+            cache.scanned = previous.scanned;
+            cache.forgotten = previous.forgotten.clone();
+            cache.tags = previous.tags.clone();
+            cache.repos = previous
+                .repos
                 .iter()
-                .filter(|p| !p.is_empty())
-                .all(|pattern| !entry_path.contains(pattern))
-        } else {
-            // Skip invalid file name
-            false
+                .filter(|entry| {
+                    entry.source == Source::Manual || !in_scope(&entry.path)
+                })
+                .cloned()
+                .collect();
+        }
+        if scope.is_none() {
+            cache.scanned = Some(cache::now());
+        }
+        for repo in repos.iter() {
+            let path = PathBuf::from(repo.path());
+            if cache.find(&path).is_some() {
+                continue;
+            }
+            let mut entry = CacheEntry::new(repo, Source::Scan);
+            if let Some(old) = previous.as_ref().and_then(|p| p.find(&path)) {
+                entry.discovered = old.discovered;
+            }
+            cache.repos.push(entry);
         }
+        cache.sort();
+        save_cache(&lock, &cache)?;
+        drop(lock);
+        let Some(previous) = previous else {
+            return Ok((vec![], vec![], Some(cache)));
+        };
+        let previous: Vec<Repo> = previous
+            .scanned_repos()
+            .into_iter()
+            .filter(|repo| in_scope(Path::new(&repo.path())))
+            .collect();
+        let added = repos
+            .iter()
+            .filter(|repo| !previous.contains(repo))
+            .cloned()
+            .collect();
+        let removed = previous
+            .into_iter()
+            .filter(|repo| !repos.contains(repo))
+            .collect();
+        Ok((added, removed, Some(cache)))
+    }
+
+    /// Clears the repos found by scans from the cache, forcing a re-scan on
+    /// the next `get_repos()` call. Repos that were added or forgotten by hand
+    /// are kept.
+    pub fn clear_cache(&mut self) -> Result<(), String> {
+        if self.cache_file.is_none() {
+            return Ok(());
+        }
+        let lock = self.lock_cache()?;
+        if let Some(mut cache) = lock.load()? {
+            cache.scanned = None;
+            cache.repos.retain(|entry| entry.source == Source::Manual);
+            save_cache(&lock, &cache)?;
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if this directory entry under `basedir` should be
+    /// included in scans.
+    fn filter(&self, basedir: &BaseDir, entry: &DirEntry) -> bool {
+        let Some(full) = entry.path().to_str() else {
+            // Skip invalid file name
+            return false;
+        };
+        // Glob patterns match against `/`-separated paths relative to the
+        // root, whatever the platform's separator.
+        let relative = entry
+            .path()
+            .strip_prefix(&basedir.path)
+            .unwrap_or(entry.path())
+            .iter()
+            .filter_map(|c| c.to_str())
+            .collect::<Vec<_>>()
+            .join("/");
+        !pattern::is_ignored(
+            self.ignored_patterns
+                .iter()
+                .chain(&basedir.ignored_patterns),
+            &relative,
+            full,
+            entry.is_dir(),
+        )
     }
 
-    /// Walks the configured base directory (and any extra roots), looking for
-    /// git repos.
-    fn find_repos(&self, extra_roots: &[PathBuf]) -> Vec<Repo> {
+    /// Walks the configured base directories (and any extra roots), looking
+    /// for git repos, and returns them along with any errors along the way.
+    fn find_repos<R: ReadDir>(
+        &self,
+        read_dir: &R,
+        extra_roots: &[PathBuf],
+    ) -> (Vec<Repo>, Vec<ScanError>) {
         let mut repos = Vec::new();
-        self.scan_root(&self.basedir.clone(), &mut repos);
-        for root in extra_roots {
-            self.scan_root(root, &mut repos);
+        let mut errors = Vec::new();
+        let extra_basedirs: Vec<BaseDir> =
+            extra_roots.iter().map(BaseDir::new).collect();
+        for basedir in self.basedirs.iter().chain(&extra_basedirs) {
+            Progress::new(self.quiet).message(format!(
+                "Scanning for git repos under {}; this may take a while...",
+                basedir.path.display()
+            ));
+            let start = &basedir.path;
+            self.scan_root(read_dir, basedir, start, &mut repos, &mut errors);
         }
         repos.sort_by_key(|r| r.path());
         repos.dedup_by_key(|r| r.path());
-        repos
+        errors.sort_by(|a, b| a.path.cmp(&b.path));
+        errors.dedup_by(|a, b| a.path == b.path);
+        (repos, errors)
     }
 
-    /// Walks a single root directory, appending discovered repos to `repos`.
-    fn scan_root(&self, root: &Path, repos: &mut Vec<Repo>) {
-        println!(
-            "Scanning for git repos under {}; this may take a while...",
-            root.display()
-        );
-        let mut n_dirs = 0;
-        let walker = WalkDir::new(root)
+    /// Walks a base directory from `start`, which is either the base
+    /// directory itself or a directory within it, appending discovered repos
+    /// to `repos` and problems to `errors`.
+    ///
+    /// The walk is spread across `default_parallelism()` threads, so repos
+    /// are found in no particular order. Once a repo is found, the walk
+    /// doesn't descend into its git directory, nor into its working tree
+    /// unless `scan_nested` is set.
+    fn scan_root<R: ReadDir>(
+        &self,
+        read_dir: &R,
+        basedir: &BaseDir,
+        start: &Path,
+        repos: &mut Vec<Repo>,
+        errors: &mut Vec<ScanError>,
+    ) {
+        let progress = Mutex::new(Progress::new(self.quiet));
+        let found = Mutex::new(Vec::new());
+        let walk_errors = Mutex::new(Vec::new());
+        let submodules = Mutex::new(HashSet::new());
+        let n_dirs = AtomicUsize::new(0);
+        let mut walker = Walker::new(start)
             .follow_links(self.follow_symlinks)
-            .same_file_system(self.same_filesystem);
-        for entry in walker
-            .into_iter()
-            .filter_entry(|e| self.filter(e))
-            .flatten()
-        {
-            if entry.file_type().is_dir() {
-                n_dirs += 1;
-                if entry.file_name() == ".git" {
-                    let parent_path = entry
-                        .path()
-                        .parent()
-                        .expect("Could not determine parent.");
-                    // Validate it's actually a valid git repo before adding.
-                    if git2::Repository::open(parent_path).is_ok() {
-                        repos.push(Repo::new(parent_path));
+            .same_file_system(self.same_filesystem)
+            .threads(default_parallelism());
+        if let Some(depth) = basedir.max_depth {
+            // A repo is marked by an entry one level below it.
+            let offset = start
+                .strip_prefix(&basedir.path)
+                .map(|rest| rest.components().count())
+                .unwrap_or(0);
+            match (depth + 1).checked_sub(offset) {
+                Some(depth) => walker = walker.max_depth(depth),
+                None => return,
+            }
+        }
+        walker.run(
+            read_dir,
+            |entry| {
+                if entry.path().to_str().is_none() {
+                    let error = ScanError::non_utf8(entry.path());
+                    walk_errors.lock().unwrap().push(error);
+                    return false;
+                }
+                self.filter(basedir, entry)
+            },
+            |entry| {
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(err) => {
+                        let error = ScanError::from_walk(err);
+                        walk_errors.lock().unwrap().push(error);
+                        return WalkState::Continue;
                     }
+                };
+                let mut state = WalkState::Continue;
+                if let Some(repo) = discover_repo(entry) {
+                    state = self.record_repo(repo, &found, &submodules);
                 }
-                if self.verbose
-                    && let Some(size) = termsize::get()
-                {
-                    let prefix = format!(
-                        "\r... found {} repos; scanning directory #{}: ",
-                        repos.len(),
-                        n_dirs
-                    );
-                    let width = size.cols as usize - prefix.len() - 1;
-                    let mut cur_path =
-                        String::from(entry.path().to_str().unwrap());
-                    let byte_width = match cur_path.char_indices().nth(width) {
-                        None => &cur_path,
-                        Some((idx, _)) => &cur_path[..idx],
+                if entry.is_dir() {
+                    let n_dirs = n_dirs.fetch_add(1, Ordering::Relaxed) + 1;
+                    if self.verbose {
+                        let n_found = found.lock().unwrap().len();
+                        let prefix = format!(
+                            "... found {} repos; scanning directory #{}: ",
+                            repos.len() + n_found,
+                            n_dirs
+                        );
+                        progress
+                            .lock()
+                            .unwrap()
+                            .status(&prefix, &entry.path().to_string_lossy());
                     }
-                    .len();
-                    cur_path.truncate(byte_width);
-                    print!("{}{:<width$}", prefix, cur_path);
                 }
+                state
+            },
+        );
+        repos.append(&mut found.into_inner().unwrap());
+        errors.append(&mut walk_errors.into_inner().unwrap());
+        progress.into_inner().unwrap().finish();
+    }
+
+    /// Records a newly found repo, unless it's a submodule of a repo found
+    /// earlier, and returns how the walk should continue past it.
+    ///
+    /// The entries of a repo's directory are all visited before any of them
+    /// are descended into, so a superproject's submodules are always known
+    /// by the time the walk reaches them.
+    fn record_repo(
+        &self,
+        repo: Repo,
+        found: &Mutex<Vec<Repo>>,
+        submodules: &Mutex<HashSet<PathBuf>>,
+    ) -> WalkState {
+        if !self.scan_nested || !repo.kind().has_work_tree() {
+            found.lock().unwrap().push(repo);
+            return WalkState::SkipSiblings;
+        }
+        let path = PathBuf::from(repo.path());
+        if submodules.lock().unwrap().contains(&path) {
+            return WalkState::SkipSiblings;
+        }
+        if let Ok(git2_repo) = git2::Repository::open(&path)
+            && let Ok(modules) = git2_repo.submodules()
+        {
+            let mut submodules = submodules.lock().unwrap();
+            for module in modules {
+                submodules.insert(path.join(module.path()));
             }
         }
-        if self.verbose {
-            println!();
+        found.lock().unwrap().push(repo);
+        // Keep walking the working tree, but not the git directory.
+        WalkState::Skip
+    }
+
+    /// Returns the path to the directory snapshot used for incremental scans,
+    /// which lives next to the cache file.
+    fn snapshot_file(&self) -> Option<PathBuf> {
+        self.cache_file
+            .as_ref()
+            .map(|file| file.with_file_name(SNAPSHOT_FILE))
+    }
+
+    /// Reads the cache file, if there is one.
+    fn load_cache(&self) -> Option<Cache> {
+        Cache::load(self.cache_file.as_ref()?)
+    }
+
+    /// Takes exclusive access to the cache file, if there is one, so that it
+    /// can be updated without racing other git-global processes.
+    fn lock_cache(&self) -> Result<CacheLock, String> {
+        let Some(file) = &self.cache_file else {
+            return Err("There is no cache file to record it in".into());
+        };
+        CacheLock::new(file)
+            .map_err(|e| format!("Could not lock cache file: {}", e))
+    }
+
+    /// Returns the repos in `cache` that still exist, limited to those
+    /// selected by `tags`, `filters`, `excludes`, `here` and `query`.
+    fn select_repos(&self, cache: &Cache) -> Vec<Repo> {
+        let tagged: Option<HashSet<&PathBuf>> =
+            (!self.tags.is_empty()).then(|| {
+                self.tags
+                    .iter()
+                    .filter_map(|tag| cache.tags.get(tag))
+                    .flatten()
+                    .collect()
+            });
+        let globs = |patterns: &[String]| -> Vec<RepoGlob> {
+            patterns.iter().map(|p| RepoGlob::new(p)).collect()
+        };
+        let filters = globs(&self.filters);
+        let excludes = globs(&self.excludes);
+        let cwd = self.here.then(|| env::current_dir().unwrap_or_default());
+        let mut repos: Vec<Repo> = cache
+            .repos
+            .iter()
+            .filter(|entry| entry.path.exists())
+            .filter(|entry| {
+                tagged
+                    .as_ref()
+                    .is_none_or(|paths| paths.contains(&entry.path))
+            })
+            .filter(|entry| {
+                filters.is_empty()
+                    || filters.iter().any(|glob| glob.is_match(&entry.path))
+            })
+            .filter(|entry| {
+                !excludes.iter().any(|glob| glob.is_match(&entry.path))
+            })
+            .filter(|entry| {
+                cwd.as_ref().is_none_or(|cwd| is_here(&entry.path, cwd))
+            })
+            .map(|entry| entry.to_repo())
+            .collect();
+        let Some(query) = self.query.clone() else {
+            return repos;
+        };
+        // Working out the state of each repo can take a while, so it's done
+        // in parallel.
+        let matching: HashSet<String> =
+            run_parallel(repos.clone(), default_parallelism(), move |repo| {
+                query.matches(repo)
+            })
+            .into_iter()
+            .filter(|(_, matches)| *matches)
+            .map(|(path, _)| path)
+            .collect();
+        repos.retain(|repo| matching.contains(&repo.path()));
+        repos
+    }
+
+    /// Returns every tag, along with the known repos that have it.
+    pub fn get_tags(&self) -> BTreeMap<String, Vec<Repo>> {
+        let Some(cache) = self.load_cache() else {
+            return BTreeMap::new();
+        };
+        cache
+            .tags
+            .iter()
+            .map(|(tag, paths)| {
+                let repos = paths
+                    .iter()
+                    .filter_map(|path| cache.find(path))
+                    .map(|entry| entry.to_repo())
+                    .collect();
+                (tag.clone(), repos)
+            })
+            .collect()
+    }
+
+    /// Adds a tag to the known repos that each target refers to, returning
+    /// the repos each target matched. A target is either the path to a repo
+    /// or a glob pattern: one with a `/` is matched against the full path of
+    /// every repo (relative to the current directory, if it's relative),
+    /// while one without is matched against just the repos' directory names.
+    pub fn tag_repos(
+        &self,
+        tag: &str,
+        targets: &[String],
+    ) -> Result<Vec<Vec<Repo>>, String> {
+        if tag.trim().is_empty() {
+            return Err("Tags can't be empty".into());
+        }
+        let lock = self.lock_cache()?;
+        let mut cache = lock.load()?.unwrap_or_default();
+        let matches: Vec<Vec<PathBuf>> = targets
+            .iter()
+            .map(|target| match_repos(&cache, target))
+            .collect();
+        let paths = cache.tags.entry(tag.to_string()).or_default();
+        paths.extend(matches.iter().flatten().cloned());
+        if paths.is_empty() {
+            cache.tags.remove(tag);
         }
+        save_cache(&lock, &cache)?;
+        Ok(to_repos(&cache, matches))
     }
 
-    /// Returns boolean indicating if the cache file exists.
-    fn has_cache(&self) -> bool {
-        self.cache_file.as_ref().is_some_and(|f| f.exists())
+    /// Removes a tag from the known repos that each target refers to (see
+    /// `tag_repos()`), or from every repo, if there are no targets. Returns
+    /// the repos each target matched that had the tag.
+    pub fn untag_repos(
+        &self,
+        tag: &str,
+        targets: &[String],
+    ) -> Result<Vec<Vec<Repo>>, String> {
+        let lock = self.lock_cache()?;
+        let mut cache = lock.load()?.unwrap_or_default();
+        let Some(mut paths) = cache.tags.remove(tag) else {
+            return Ok(vec![vec![]; targets.len().max(1)]);
+        };
+        let matches: Vec<Vec<PathBuf>> = if targets.is_empty() {
+            vec![paths.iter().cloned().collect()]
+        } else {
+            targets
+                .iter()
+                .map(|target| {
+                    let mut matched = match_repos(&cache, target);
+                    matched.retain(|path| paths.contains(path));
+                    matched
+                })
+                .collect()
+        };
+        for path in matches.iter().flatten() {
+            paths.remove(path);
+        }
+        if !paths.is_empty() {
+            cache.tags.insert(tag.to_string(), paths);
+        }
+        save_cache(&lock, &cache)?;
+        Ok(to_repos(&cache, matches))
     }
 
-    /// Writes the given repo paths to the cache file.
-    fn cache_repos(&self, repos: &[Repo]) {
-        if let Some(file) = &self.cache_file {
-            if !file.exists()
-                && let Some(parent) = &file.parent()
-            {
-                create_dir_all(parent)
-                    .expect("Could not create cache directory.")
-            }
-            let mut f =
-                File::create(file).expect("Could not create cache file.");
-            for repo in repos.iter() {
-                match writeln!(f, "{}", repo.path()) {
-                    Ok(_) => (),
-                    Err(e) => panic!("Problem writing cache file: {}", e),
-                }
-            }
+    /// Removes every repo from the cache that no longer exists or is no
+    /// longer a valid git repo, whether a scan found it or it was added by
+    /// hand, and returns the removed repos, sorted by path, with the reason
+    /// each was removed. With `dry_run`, the cache is left as it is.
+    pub fn prune_cache(
+        &self,
+        dry_run: bool,
+    ) -> Result<Vec<(Repo, PruneReason)>, String> {
+        let lock = self.lock_cache()?;
+        let Some(mut cache) = lock.load()? else {
+            return Ok(vec![]);
+        };
+        let mut pruned = Vec::new();
+        cache.repos.retain(|entry| {
+            let reason = if !entry.path.exists() {
+                PruneReason::Missing
+            } else if git2::Repository::open(&entry.path).is_err() {
+                PruneReason::NotARepo
+            } else {
+                return true;
+            };
+            pruned.push((entry.to_repo(), reason));
+            false
+        });
+        for paths in cache.tags.values_mut() {
+            paths.retain(|path| {
+                !pruned
+                    .iter()
+                    .any(|(repo, _)| Path::new(&repo.path()) == path)
+            });
         }
+        cache.tags.retain(|_, paths| !paths.is_empty());
+        if !dry_run && !pruned.is_empty() {
+            save_cache(&lock, &cache)?;
+        }
+        Ok(pruned)
     }
 
-    /// Returns the list of repos found in the cache file.
-    fn get_cached_repos(&self) -> Vec<Repo> {
-        let mut repos = Vec::new();
-        if let Some(file) = &self.cache_file
-            && file.exists()
-        {
-            let f = File::open(file).expect("Could not open cache file.");
-            let reader = BufReader::new(f);
-            for repo_path in reader.lines().map_while(Result::ok) {
-                if !Path::new(&repo_path).exists() {
-                    continue;
-                }
-                repos.push(Repo::new(repo_path))
+    /// Registers a repo by hand, so that it's known whether or not any scan
+    /// finds it. Undoes `forget_repo()` for the repo, if necessary.
+    ///
+    /// Fails if the path isn't a git repo.
+    pub fn add_repo(&self, path: &Path) -> Result<Repo, String> {
+        let git2_repo = git2::Repository::open(path).map_err(|e| {
+            format!("{} is not a git repo: {}", path.display(), e.message())
+        })?;
+        let path = normalize(git2_repo.workdir().unwrap_or(git2_repo.path()));
+        let kind = RepoKind::detect(&path).unwrap_or(RepoKind::Normal);
+        let repo = Repo::with_kind(&path, kind);
+        let lock = self.lock_cache()?;
+        let mut cache = lock.load()?.unwrap_or_default();
+        cache.forgotten.retain(|p| *p != path);
+        let mut entry = CacheEntry::new(&repo, Source::Manual);
+        if let Some(old) = cache.find(&path) {
+            entry.discovered = old.discovered;
+        }
+        cache.repos.retain(|e| e.path != path);
+        cache.repos.push(entry);
+        cache.sort();
+        save_cache(&lock, &cache)?;
+        Ok(repo)
+    }
+
+    /// Forgets a repo, so that it's no longer known, even if a scan finds it,
+    /// until it's added again with `add_repo()`.
+    pub fn forget_repo(&self, path: &Path) -> Result<Repo, String> {
+        // The repo may already be gone, in which case the path is taken as
+        // it is.
+        let path = match git2::Repository::open(path) {
+            Ok(repo) => normalize(repo.workdir().unwrap_or(repo.path())),
+            Err(_) => {
+                normalize(&env::current_dir().unwrap_or_default().join(path))
             }
+        };
+        let lock = self.lock_cache()?;
+        let mut cache = lock.load()?.unwrap_or_default();
+        cache.repos.retain(|e| e.path != path);
+        if !cache.forgotten.contains(&path) {
+            cache.forgotten.push(path.clone());
         }
-        repos
+        save_cache(&lock, &cache)?;
+        Ok(Repo::new(path))
     }
 
     /// Adds a pattern to the global.ignore setting in gitconfig.
     ///
     /// Uses the gitconfig path that was determined when this `Config` was
     /// created (explicit path for `from_homedir`, system default for `new`).
-    pub fn add_ignore_pattern(&self, pattern: &str) -> Result<(), String> {
+    /// Unless it says otherwise, the pattern is a glob, and it's returned as
+    /// written, with a `glob:` prefix if that's needed to make it one.
+    pub fn add_ignore_pattern(&self, pattern: &str) -> Result<String, String> {
         let mut cfg = match &self.git_config_path {
             Some(path) => git2::Config::open(path),
             None => git2::Config::open_default(),
         }
         .map_err(|e| format!("Could not open git config: {}", e))?;
 
+        let pattern = IgnorePattern::with_style(
+            pattern,
+            PatternStyle::Glob,
+            self.ignore_style,
+        );
+        let pattern = pattern.as_str();
+
         // Get current patterns
         let current = cfg.get_string(SETTING_IGNORE).unwrap_or_default();
         let patterns: Vec<&str> = current
@@ -375,6 +1261,197 @@ impl Config {
         cfg.set_str(SETTING_IGNORE, &new_value)
             .map_err(|e| format!("Could not update git config: {}", e))?;
 
-        Ok(())
+        Ok(pattern.to_string())
+    }
+}
+
+/// Returns the paths of the known repos that a target of `tag_repos()`
+/// refers to.
+fn match_repos(cache: &Cache, target: &str) -> Vec<PathBuf> {
+    let cwd = env::current_dir().unwrap_or_default();
+    let path = normalize(&cwd.join(target));
+    if cache.find(&path).is_some() {
+        return vec![path];
+    }
+    let glob = RepoGlob::new(target);
+    cache
+        .repos
+        .iter()
+        .map(|entry| &entry.path)
+        .filter(|path| glob.is_match(path))
+        .cloned()
+        .collect()
+}
+
+/// A glob pattern that picks out repos. A pattern with a `/` is matched
+/// against the full path of a repo (relative to the current directory, if
+/// it's relative), while one without is matched against just the name of the
+/// repo's directory.
+struct RepoGlob {
+    glob: Glob,
+    full_path: bool,
+}
+
+impl RepoGlob {
+    fn new(pattern: &str) -> RepoGlob {
+        if pattern.contains('/') {
+            let cwd = env::current_dir().unwrap_or_default();
+            let path = normalize(&cwd.join(pattern));
+            RepoGlob {
+                glob: Glob::new(&path.to_string_lossy()),
+                full_path: true,
+            }
+        } else {
+            RepoGlob {
+                glob: Glob::new(pattern),
+                full_path: false,
+            }
+        }
+    }
+
+    fn is_match(&self, path: &Path) -> bool {
+        let subject = if self.full_path {
+            path.to_str()
+        } else {
+            path.file_name().and_then(|name| name.to_str())
+        };
+        subject.is_some_and(|subject| self.glob.is_match(subject))
+    }
+}
+
+/// Returns `true` if a repo is in or under the given directory, or if the
+/// directory is inside the repo.
+fn is_here(path: &Path, cwd: &Path) -> bool {
+    let related = |path: &Path| path.starts_with(cwd) || cwd.starts_with(path);
+    // The current directory has its symbolic links resolved, while a repo's
+    // path may not.
+    related(path) || path.canonicalize().is_ok_and(|real| related(&real))
+}
+
+/// Turns lists of repo paths into lists of repos.
+fn to_repos(cache: &Cache, lists: Vec<Vec<PathBuf>>) -> Vec<Vec<Repo>> {
+    lists
+        .into_iter()
+        .map(|paths| {
+            paths
+                .iter()
+                .map(|path| match cache.find(path) {
+                    Some(entry) => entry.to_repo(),
+                    None => Repo::new(path),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Returns how long ago the given time was, given in seconds since the Unix
+/// epoch.
+fn age(time: u64) -> Duration {
+    Duration::from_secs(cache::now().saturating_sub(time))
+}
+
+/// Writes the cache file, while holding its lock.
+fn save_cache(lock: &CacheLock, cache: &Cache) -> Result<(), String> {
+    lock.save(cache)
+        .map_err(|e| format!("Could not write cache file: {}", e))
+}
+
+/// Parses a duration like `90`, `90s`, `30m`, `12h`, `7d` or `2w`. A bare
+/// number is a number of seconds.
+pub(crate) fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let seconds = match unit.trim() {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+    let number: u64 = number.parse().ok()?;
+    Some(Duration::from_secs(number.checked_mul(seconds)?))
+}
+
+/// Resolves `.` and `..` components and drops any trailing separator.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normal = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                normal.pop();
+            }
+            c => normal.push(c),
+        }
+    }
+    normal
+}
+
+/// Parses a comma-separated list of ignore patterns.
+fn parse_patterns(list: &str, style: PatternStyle) -> Vec<IgnorePattern> {
+    list.split(',')
+        .map(|p| p.trim())
+        .filter(|p| !p.is_empty())
+        .map(|p| IgnorePattern::new(p, style))
+        .collect()
+}
+
+/// Returns the repo that a directory entry marks, if any.
+///
+/// A `.git` directory or file marks its parent as a repo. A `HEAD` file marks
+/// its parent as a bare repo, unless that parent is part of another repo's
+/// git directory (such as `.git/modules/<name>` or `.git/worktrees/<name>`).
+fn discover_repo(entry: &DirEntry) -> Option<Repo> {
+    let parent_path = entry.path().parent()?;
+    if entry.file_name() == ".git" {
+        let kind = RepoKind::detect(parent_path)?;
+        // Validate it's actually a valid git repo before adding.
+        git2::Repository::open(parent_path).ok()?;
+        Some(Repo::with_kind(parent_path, kind))
+    } else if entry.file_name() == "HEAD" && !entry.is_dir() {
+        if parent_path.iter().any(|c| c == ".git") {
+            return None;
+        }
+        match RepoKind::detect(parent_path)? {
+            RepoKind::Bare => {
+                Some(Repo::with_kind(parent_path, RepoKind::Bare))
+            }
+            _ => None,
+        }
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_here() {
+        let cwd = Path::new("/src/work");
+        assert!(is_here(Path::new("/src/work"), cwd));
+        assert!(is_here(Path::new("/src/work/api"), cwd));
+        assert!(is_here(Path::new("/src"), cwd));
+        assert!(!is_here(Path::new("/src/home"), cwd));
+        assert!(!is_here(Path::new("/src/workshop"), cwd));
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        let secs = |n| Some(Duration::from_secs(n));
+        assert_eq!(parse_duration("90"), secs(90));
+        assert_eq!(parse_duration("90s"), secs(90));
+        assert_eq!(parse_duration("30m"), secs(30 * 60));
+        assert_eq!(parse_duration(" 12h "), secs(12 * 60 * 60));
+        assert_eq!(parse_duration("7d"), secs(7 * 24 * 60 * 60));
+        assert_eq!(parse_duration("2w"), secs(14 * 24 * 60 * 60));
+        assert_eq!(parse_duration("h"), None);
+        assert_eq!(parse_duration("1y"), None);
+        assert_eq!(parse_duration("-1"), None);
     }
 }
@@ -9,6 +9,8 @@ use std::result;
 #[derive(Debug)]
 pub enum GitGlobalError {
     BadSubcommand(String),
+    /// A subcommand couldn't do what it was asked to, for the given reason.
+    Failed(String),
     Generic,
 }
 
@@ -22,6 +24,7 @@ impl fmt::Display for GitGlobalError {
             BadSubcommand(ref cmd) => {
                 write!(f, "Unknown subcommand \"{}\".", cmd)
             }
+            Failed(ref reason) => write!(f, "{}", reason),
             Generic => write!(f, "An error occured :(."),
         }
     }
@@ -32,6 +35,7 @@ impl Error for GitGlobalError {
         use GitGlobalError::*;
         match *self {
             BadSubcommand(_) => "unknown subcommand",
+            Failed(_) => "subcommand failed",
             Generic => "an error occurred :(",
         }
     }
@@ -0,0 +1,128 @@
+//! A minimal, safe wrapper around Linux's inotify API, for the `watch`
+//! subcommand.
+
+use std::ffi::{CString, OsStr, OsString};
+use std::io;
+use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::time::Duration;
+
+pub use libc::{
+    IN_CREATE, IN_DELETE, IN_IGNORED, IN_ISDIR, IN_MOVED_FROM, IN_MOVED_TO,
+    IN_ONLYDIR, IN_Q_OVERFLOW,
+};
+
+/// A watch descriptor, identifying a watched path.
+pub type WatchId = i32;
+
+/// A single filesystem event.
+#[derive(Debug)]
+pub struct Event {
+    pub wd: WatchId,
+    pub mask: u32,
+    /// The name of the entry the event is about, within the watched
+    /// directory, or `None` if it's about the directory itself.
+    pub name: Option<OsString>,
+}
+
+/// An inotify instance.
+pub struct Inotify {
+    fd: OwnedFd,
+}
+
+impl Inotify {
+    /// Create a new inotify instance.
+    pub fn new() -> io::Result<Inotify> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Inotify {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+        })
+    }
+
+    /// Starts watching a path for the events in `mask`, returning the watch
+    /// descriptor. Watching a path again returns the same descriptor.
+    pub fn add_watch(&self, path: &Path, mask: u32) -> io::Result<WatchId> {
+        let path = CString::new(path.as_os_str().as_bytes())?;
+        let wd = unsafe {
+            libc::inotify_add_watch(self.fd.as_raw_fd(), path.as_ptr(), mask)
+        };
+        if wd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(wd)
+    }
+
+    /// Stops watching a path. Errors are ignored, since the kernel drops
+    /// watches on its own when their paths are deleted.
+    pub fn rm_watch(&self, wd: WatchId) {
+        unsafe {
+            libc::inotify_rm_watch(self.fd.as_raw_fd(), wd);
+        }
+    }
+
+    /// Waits up to `timeout` (or forever, if `None`) for events, returning
+    /// whatever events are available, which may be none.
+    pub fn read_events(
+        &self,
+        timeout: Option<Duration>,
+    ) -> io::Result<Vec<Event>> {
+        let mut pollfd = libc::pollfd {
+            fd: self.fd.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout = timeout
+            .map(|t| t.as_millis().min(i32::MAX as u128) as i32)
+            .unwrap_or(-1);
+        let n = unsafe { libc::poll(&mut pollfd, 1, timeout) };
+        if n < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                return Ok(vec![]);
+            }
+            return Err(err);
+        }
+        if n == 0 {
+            return Ok(vec![]);
+        }
+        let mut buf = [0u8; 64 * 1024];
+        let len = unsafe {
+            libc::read(
+                self.fd.as_raw_fd(),
+                buf.as_mut_ptr() as *mut libc::c_void,
+                buf.len(),
+            )
+        };
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(parse_events(&buf[..len as usize]))
+    }
+}
+
+/// Parses the `inotify_event` records that a read returned.
+fn parse_events(mut buf: &[u8]) -> Vec<Event> {
+    let header = mem::size_of::<libc::inotify_event>();
+    let mut events = Vec::new();
+    while buf.len() >= header {
+        let raw: libc::inotify_event =
+            unsafe { std::ptr::read_unaligned(buf.as_ptr() as *const _) };
+        let end = (header + raw.len as usize).min(buf.len());
+        // The name is padded with NUL bytes.
+        let name = &buf[header..end];
+        let name =
+            &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+        events.push(Event {
+            wd: raw.wd,
+            mask: raw.mask,
+            name: (!name.is_empty()).then(|| OsStr::from_bytes(name).into()),
+        });
+        buf = &buf[end..];
+    }
+    events
+}
@@ -49,18 +49,31 @@
 //! [rfcl]: fn.run_from_command_line.html
 //! [subcommands]: subcommands/index.html
 
+mod cache;
 mod cli;
 mod config;
 mod errors;
+#[cfg(target_os = "linux")]
+mod inotify;
 mod parallel;
+mod pattern;
+mod progress;
+mod query;
 mod repo;
 mod report;
+mod snapshot;
 pub mod subcommands; // Using `pub mod` so we see the docs.
+mod walk;
 
 pub use cli::{get_clap_app, run_from_command_line};
-pub use config::Config;
+pub use config::{
+    BaseDir, Config, PruneReason, ScanError, ScanErrorKind, ScanMode,
+    ScanResult,
+};
 pub use errors::{GitGlobalError, Result};
-pub use repo::Repo;
+pub use pattern::{IgnorePattern, PatternStyle};
+pub use query::Query;
+pub use repo::{Fact, FactValue, Repo, RepoKind, Tracking};
 pub use report::Report;
 
 #[doc(hidden)]
@@ -0,0 +1,494 @@
+//! Path patterns for ignoring directories while scanning for repos.
+//!
+//! Each entry in `global.ignore` is either a glob pattern, which follows the
+//! rules of a `.gitignore` file, or a substring pattern, which (as in older
+//! versions of git-global) ignores any path that contains it.
+//!
+//! Glob patterns are matched against paths relative to the directory being
+//! scanned:
+//!
+//! * `*` matches anything but `/`, `?` matches any one character but `/`, and
+//!   `[a-z]` matches one character in a set (`[!a-z]` for the complement).
+//! * `**/` at the start or `/**/` in the middle of a pattern match zero or
+//!   more directories, and `/**` at the end matches everything inside.
+//! * A pattern with a `/` at the start or in the middle is anchored to the
+//!   scanned directory; otherwise, it matches a name at any depth.
+//! * A pattern ending with `/` only matches directories.
+//! * A pattern starting with `!` re-includes paths that an earlier pattern
+//!   ignored. The last matching pattern wins, but nothing can be re-included
+//!   from inside an ignored directory, since it isn't walked.
+//!
+//! Unprefixed patterns use the style from `global.ignore-style`; a `glob:` or
+//! `substring:` prefix picks the style for a single pattern. So that patterns
+//! written for older versions keep ignoring the same paths, the default style
+//! is substring, and `git global ignore` writes new patterns with a `glob:`
+//! prefix unless `global.ignore-style` is `glob`.
+
+use std::fmt;
+
+const GLOB_PREFIX: &str = "glob:";
+const SUBSTRING_PREFIX: &str = "substring:";
+
+/// How an ignore pattern is matched against paths.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PatternStyle {
+    /// `.gitignore`-style glob matching.
+    Glob,
+    /// Matches any path containing the pattern.
+    Substring,
+}
+
+impl PatternStyle {
+    /// Returns the style with the given name, as used in `global.ignore-style`.
+    pub fn from_name(name: &str) -> Option<PatternStyle> {
+        match name.trim() {
+            "glob" => Some(PatternStyle::Glob),
+            "substring" => Some(PatternStyle::Substring),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for PatternStyle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PatternStyle::Glob => write!(f, "glob"),
+            PatternStyle::Substring => write!(f, "substring"),
+        }
+    }
+}
+
+/// A single element of a compiled glob.
+#[derive(Clone, Debug)]
+enum Token {
+    Char(char),
+    /// `?`
+    Any,
+    /// `*`
+    Star,
+    /// A trailing `**`.
+    DoubleStar,
+    /// A leading `**/` or inner `/**/`; matches zero or more directories.
+    AnyDirs,
+    /// `[...]`
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+/// A compiled glob, matched against `/`-separated paths, or against any
+/// text.
+#[derive(Clone, Debug)]
+pub struct Glob {
+    tokens: Vec<Token>,
+    /// Whether `/` separates path components, which only `**` can match.
+    paths: bool,
+}
+
+impl Glob {
+    /// Compiles a glob pattern for matching paths.
+    pub fn new(pattern: &str) -> Glob {
+        Glob::compile(pattern, true)
+    }
+
+    /// Compiles a glob pattern for matching text that isn't a path, in
+    /// which `*`, `?` and classes match `/` like any other character.
+    pub fn text(pattern: &str) -> Glob {
+        Glob::compile(pattern, false)
+    }
+
+    fn compile(pattern: &str, paths: bool) -> Glob {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut tokens = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            match chars[i] {
+                '\\' if i + 1 < chars.len() => {
+                    tokens.push(Token::Char(chars[i + 1]));
+                    i += 2;
+                    continue;
+                }
+                '?' => tokens.push(Token::Any),
+                '*' => {
+                    let start = i;
+                    while i + 1 < chars.len() && chars[i + 1] == '*' {
+                        i += 1;
+                    }
+                    let bounded_before = start == 0 || chars[start - 1] == '/';
+                    if !paths {
+                        tokens.push(Token::DoubleStar);
+                    } else if i > start && bounded_before {
+                        if i + 1 == chars.len() {
+                            tokens.push(Token::DoubleStar);
+                        } else if chars[i + 1] == '/' {
+                            tokens.push(Token::AnyDirs);
+                            i += 1;
+                        } else {
+                            tokens.push(Token::Star);
+                        }
+                    } else {
+                        tokens.push(Token::Star);
+                    }
+                }
+                '[' => match parse_class(&chars[i + 1..]) {
+                    Some((token, len)) => {
+                        tokens.push(token);
+                        i += len;
+                    }
+                    None => tokens.push(Token::Char('[')),
+                },
+                c => tokens.push(Token::Char(c)),
+            }
+            i += 1;
+        }
+        Glob {
+            tokens,
+            paths,
+        }
+    }
+
+    /// Returns `true` if the whole of `path` matches the glob.
+    pub fn is_match(&self, path: &str) -> bool {
+        let chars: Vec<char> = path.chars().collect();
+        match_tokens(&self.tokens, &chars, self.paths)
+    }
+}
+
+/// Parses a character class, given the characters after its opening `[`.
+/// Returns the class and the number of characters it used, including the
+/// closing `]`, or `None` if the class isn't closed.
+fn parse_class(chars: &[char]) -> Option<(Token, usize)> {
+    let mut i = 0;
+    let negated = matches!(chars.first(), Some('!') | Some('^'));
+    if negated {
+        i += 1;
+    }
+    let mut ranges = Vec::new();
+    let mut first = true;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == ']' && !first {
+            return Some((
+                Token::Class {
+                    negated,
+                    ranges,
+                },
+                i + 1,
+            ));
+        }
+        first = false;
+        if i + 2 < chars.len() && chars[i + 1] == '-' && chars[i + 2] != ']' {
+            ranges.push((c, chars[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((c, c));
+            i += 1;
+        }
+    }
+    None
+}
+
+/// Matches a sequence of tokens against the whole of `text`, in which `/`
+/// is only matched by `**`, or explicitly, if it separates `paths`.
+fn match_tokens(tokens: &[Token], text: &[char], paths: bool) -> bool {
+    let mut state = MatchState {
+        tokens,
+        text,
+        paths,
+        memo: vec![None; (tokens.len() + 1) * (text.len() + 1)],
+    };
+    state.matches(0, 0)
+}
+
+/// Matching in progress. Each star can backtrack over the rest of the text,
+/// so whether the tokens from one index on match the text from another is
+/// remembered; otherwise, patterns with several stars would take time
+/// exponential in the length of the text.
+struct MatchState<'a> {
+    tokens: &'a [Token],
+    text: &'a [char],
+    paths: bool,
+    memo: Vec<Option<bool>>,
+}
+
+impl MatchState<'_> {
+    /// Returns `true` if the tokens from `t` on match the text from `i` on.
+    fn matches(&mut self, t: usize, i: usize) -> bool {
+        let key = t * (self.text.len() + 1) + i;
+        if let Some(matched) = self.memo[key] {
+            return matched;
+        }
+        let matched = self.match_token(t, i);
+        self.memo[key] = Some(matched);
+        matched
+    }
+
+    fn match_token(&mut self, t: usize, i: usize) -> bool {
+        let (text, paths) = (self.text, self.paths);
+        let Some(token) = self.tokens.get(t) else {
+            return i == text.len();
+        };
+        let next = text.get(i).copied();
+        match token {
+            Token::Char(c) => next == Some(*c) && self.matches(t + 1, i + 1),
+            Token::Any => {
+                next.is_some_and(|c| !paths || c != '/')
+                    && self.matches(t + 1, i + 1)
+            }
+            Token::Class {
+                negated,
+                ranges,
+            } => {
+                next.is_some_and(|c| {
+                    let in_class =
+                        ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi);
+                    (!paths || c != '/') && in_class != *negated
+                }) && self.matches(t + 1, i + 1)
+            }
+            Token::Star => {
+                let end = text[i..]
+                    .iter()
+                    .position(|&c| c == '/')
+                    .map_or(text.len(), |n| i + n);
+                (i..=end).any(|j| self.matches(t + 1, j))
+            }
+            Token::DoubleStar => {
+                (i..=text.len()).any(|j| self.matches(t + 1, j))
+            }
+            Token::AnyDirs => {
+                self.matches(t + 1, i)
+                    || (i..text.len())
+                        .filter(|&j| text[j] == '/')
+                        .any(|j| self.matches(t + 1, j + 1))
+            }
+        }
+    }
+}
+
+/// The compiled form of an ignore pattern.
+#[derive(Clone, Debug)]
+enum Matcher {
+    Glob {
+        glob: Glob,
+        anchored: bool,
+        dir_only: bool,
+    },
+    Substring(String),
+}
+
+/// A pattern from `global.ignore`.
+#[derive(Clone, Debug)]
+pub struct IgnorePattern {
+    raw: String,
+    negated: bool,
+    matcher: Matcher,
+}
+
+impl IgnorePattern {
+    /// Parses a pattern from `global.ignore`, using `default_style` unless
+    /// the pattern has a `glob:` or `substring:` prefix.
+    pub fn new(raw: &str, default_style: PatternStyle) -> IgnorePattern {
+        let (style, pattern) = if let Some(p) = raw.strip_prefix(GLOB_PREFIX) {
+            (PatternStyle::Glob, p)
+        } else if let Some(p) = raw.strip_prefix(SUBSTRING_PREFIX) {
+            (PatternStyle::Substring, p)
+        } else {
+            (default_style, raw)
+        };
+        let (negated, matcher) = match style {
+            PatternStyle::Substring => {
+                (false, Matcher::Substring(pattern.to_string()))
+            }
+            PatternStyle::Glob => {
+                let negated = pattern.starts_with('!');
+                let pattern = pattern.strip_prefix('!').unwrap_or(pattern);
+                let dir_only = pattern.ends_with('/');
+                let pattern = pattern.trim_end_matches('/');
+                let anchored = pattern.contains('/');
+                let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+                (
+                    negated,
+                    Matcher::Glob {
+                        glob: Glob::new(pattern),
+                        anchored,
+                        dir_only,
+                    },
+                )
+            }
+        };
+        IgnorePattern {
+            raw: raw.to_string(),
+            negated,
+            matcher,
+        }
+    }
+
+    /// Returns `raw` as it should be written to `global.ignore` to be
+    /// matched in the given style, when unprefixed patterns use
+    /// `default_style`.
+    pub fn with_style(
+        raw: &str,
+        style: PatternStyle,
+        default_style: PatternStyle,
+    ) -> String {
+        let prefixed =
+            raw.starts_with(GLOB_PREFIX) || raw.starts_with(SUBSTRING_PREFIX);
+        match style {
+            _ if prefixed || style == default_style => raw.to_string(),
+            PatternStyle::Glob => format!("{}{}", GLOB_PREFIX, raw),
+            PatternStyle::Substring => format!("{}{}", SUBSTRING_PREFIX, raw),
+        }
+    }
+
+    /// Returns how this pattern is matched against paths.
+    pub fn style(&self) -> PatternStyle {
+        match self.matcher {
+            Matcher::Glob {
+                ..
+            } => PatternStyle::Glob,
+            Matcher::Substring(_) => PatternStyle::Substring,
+        }
+    }
+
+    /// Returns `true` if this pattern re-includes the paths it matches.
+    pub fn is_negated(&self) -> bool {
+        self.negated
+    }
+
+    /// Returns `true` if the pattern matches a path, given both relative to
+    /// the scanned directory (with `/` separators) and in full.
+    fn is_match(&self, relative: &str, full: &str, is_dir: bool) -> bool {
+        match self.matcher {
+            Matcher::Substring(ref s) => full.contains(s.as_str()),
+            Matcher::Glob {
+                ref glob,
+                anchored,
+                dir_only,
+            } => {
+                if relative.is_empty() || (dir_only && !is_dir) {
+                    return false;
+                }
+                if anchored {
+                    glob.is_match(relative)
+                } else {
+                    let name = relative.rsplit('/').next().unwrap_or(relative);
+                    glob.is_match(name)
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for IgnorePattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+/// Returns `true` if the last of `patterns` to match a path ignores it.
+pub fn is_ignored<'a, I>(
+    patterns: I,
+    relative: &str,
+    full: &str,
+    is_dir: bool,
+) -> bool
+where
+    I: IntoIterator<Item = &'a IgnorePattern>,
+    I::IntoIter: DoubleEndedIterator,
+{
+    patterns
+        .into_iter()
+        .rev()
+        .find(|p| p.is_match(relative, full, is_dir))
+        .is_some_and(|p| !p.negated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ignored(patterns: &[&str], relative: &str, is_dir: bool) -> bool {
+        let patterns: Vec<IgnorePattern> = patterns
+            .iter()
+            .map(|p| IgnorePattern::new(p, PatternStyle::Glob))
+            .collect();
+        let full = format!("/home/user/{}", relative);
+        is_ignored(&patterns, relative, &full, is_dir)
+    }
+
+    #[test]
+    fn test_glob() {
+        assert!(Glob::new("*.rs").is_match("main.rs"));
+        assert!(!Glob::new("*.rs").is_match("src/main.rs"));
+        assert!(Glob::new("src/*.rs").is_match("src/main.rs"));
+        assert!(Glob::new("**/target").is_match("target"));
+        assert!(Glob::new("**/target").is_match("a/b/target"));
+        assert!(Glob::new("a/**/b").is_match("a/b"));
+        assert!(Glob::new("a/**/b").is_match("a/x/y/b"));
+        assert!(!Glob::new("a/**/b").is_match("ab"));
+        assert!(Glob::new("a/**").is_match("a/x/y"));
+        assert!(Glob::new("ba?").is_match("bar"));
+        assert!(Glob::new("[a-c]x").is_match("bx"));
+        assert!(!Glob::new("[!a-c]x").is_match("bx"));
+        assert!(Glob::new("\\*").is_match("*"));
+        assert!(!Glob::new("\\*").is_match("x"));
+        assert!(Glob::text("*github.com*").is_match("git@github.com:a/b.git"));
+        assert!(Glob::text("feat?re/*").is_match("feature/x/y"));
+        assert!(Glob::text("a?b").is_match("a/b"));
+        assert!(!Glob::text("*.org").is_match("example.com"));
+    }
+
+    #[test]
+    fn test_many_stars_match_quickly() {
+        // Without memoization, these take time exponential in the length of
+        // the text, since each star backtracks over the rest of it.
+        let text = "a".repeat(200);
+        assert!(!Glob::new("*a*a*a*a*a*a*a*a*a*a*b").is_match(&text));
+        assert!(!Glob::text("*a*a*a*a*a*a*a*a*a*a*b").is_match(&text));
+        let path = vec!["a"; 100].join("/");
+        assert!(!Glob::new("**/a/**/a/**/a/**/a/**/a/**/b").is_match(&path));
+        assert!(Glob::new("**/a/**/a/**/a/**/a/**/a/**").is_match(&path));
+    }
+
+    #[test]
+    fn test_unanchored_patterns_match_names_at_any_depth() {
+        assert!(ignored(&["build"], "build", true));
+        assert!(ignored(&["build"], "src/project/build", true));
+        assert!(!ignored(&["build"], "src/rebuild-tool", true));
+        assert!(ignored(&["*.bak"], "src/old.bak", false));
+    }
+
+    #[test]
+    fn test_anchored_patterns() {
+        assert!(ignored(&["/build"], "build", true));
+        assert!(!ignored(&["/build"], "src/build", true));
+        assert!(ignored(&["src/build"], "src/build", true));
+        assert!(!ignored(&["src/build"], "x/src/build", true));
+        assert!(ignored(&["**/node_modules"], "a/b/node_modules", true));
+    }
+
+    #[test]
+    fn test_directory_only_and_negated_patterns() {
+        assert!(ignored(&["cache/"], "a/cache", true));
+        assert!(!ignored(&["cache/"], "a/cache", false));
+        assert!(!ignored(&["vendor*", "!vendor-keep"], "vendor-keep", true));
+        assert!(ignored(&["vendor*", "!vendor-keep"], "vendor-other", true));
+    }
+
+    #[test]
+    fn test_substring_patterns() {
+        let substring = IgnorePattern::new("build", PatternStyle::Substring);
+        assert_eq!(substring.style(), PatternStyle::Substring);
+        assert!(is_ignored(&[substring], "rebuild", "/x/rebuild", true));
+
+        let prefixed = IgnorePattern::new("substring:!x", PatternStyle::Glob);
+        assert_eq!(prefixed.style(), PatternStyle::Substring);
+        assert!(!prefixed.is_negated());
+        assert!(is_ignored(&[prefixed], "a!x", "/a!x", false));
+
+        let glob = IgnorePattern::new("glob:build", PatternStyle::Substring);
+        assert_eq!(glob.style(), PatternStyle::Glob);
+        assert!(!is_ignored(&[glob], "rebuild", "/x/rebuild", true));
+    }
+}
@@ -0,0 +1,77 @@
+//! Progress output for long-running work, like scans.
+//!
+//! Progress always goes to stderr, so that stdout only ever carries a
+//! subcommand's report (which may be JSON). It's suppressed entirely when
+//! stderr isn't a terminal, or when the user asks for quiet.
+
+use std::fmt::Display;
+use std::io::{self, IsTerminal, Write};
+
+/// The width to assume when the terminal's width can't be determined.
+const DEFAULT_WIDTH: usize = 80;
+
+/// A channel for progress output.
+pub struct Progress {
+    enabled: bool,
+    quiet: bool,
+    /// Whether the status line has been written to since it was last ended.
+    in_status: bool,
+}
+
+impl Progress {
+    /// Create a new `Progress`, which prints nothing if `quiet` is set or
+    /// stderr isn't a terminal.
+    pub fn new(quiet: bool) -> Progress {
+        Progress {
+            enabled: !quiet && io::stderr().is_terminal(),
+            quiet,
+            in_status: false,
+        }
+    }
+
+    /// Prints a line of progress, ending the status line first, if needed.
+    pub fn message<T: Display>(&mut self, message: T) {
+        if self.enabled {
+            self.finish();
+            eprintln!("{}", message);
+        }
+    }
+
+    /// Prints a warning, ending the status line first, if needed. Unlike
+    /// progress, warnings are printed whether or not stderr is a terminal,
+    /// unless the user asks for quiet.
+    pub fn warn<T: Display>(&mut self, warning: T) {
+        if !self.quiet {
+            self.finish();
+            eprintln!("warning: {}", warning);
+        }
+    }
+
+    /// Replaces the status line: a single line that ends with a path, which
+    /// is truncated to fit the terminal.
+    pub fn status(&mut self, prefix: &str, path: &str) {
+        if !self.enabled {
+            return;
+        }
+        let cols = termsize::get()
+            .map(|size| size.cols as usize)
+            .unwrap_or(DEFAULT_WIDTH);
+        let width = cols.saturating_sub(prefix.chars().count() + 1);
+        let path = match path.char_indices().nth(width) {
+            None => path,
+            Some((idx, _)) => &path[..idx],
+        };
+        let mut stderr = io::stderr().lock();
+        let _ = write!(stderr, "\r{}{:<width$}", prefix, path);
+        let _ = stderr.flush();
+        self.in_status = true;
+    }
+
+    /// Ends the status line, if there is one.
+    pub fn finish(&mut self) {
+        if self.in_status {
+            eprintln!();
+            self.in_status = false;
+        }
+    }
+}
@@ -0,0 +1,364 @@
+//! A small expression language for selecting repos by their state.
+//!
+//! The global `--where` option takes an expression over facts about each
+//! repo, and limits every subcommand to the repos for which it holds:
+//!
+//! ```bash
+//! $ git global list --where 'dirty && !has_remote'
+//! $ git global status --where 'ahead > 0 || stashes > 0'
+//! $ git global list --where 'branch != "main" && age > 30d'
+//! $ git global list --where 'remote ~ "*github.com*"'
+//! ```
+//!
+//! The facts are `dirty`, `staged`, `unstaged`, `untracked` and `has_remote`
+//! (true or false); `ahead`, `behind` and `stashes` (counts); `age` (seconds
+//! since the current commit); and `branch` and `remote` (text, or missing for
+//! a detached HEAD or a repo without remotes). See `repo::Fact` for details.
+//!
+//! Values are compared with `==`, `!=`, `<`, `<=`, `>` and `>=`, and text can
+//! be matched against a quoted glob pattern with `~`, where `*` matches any
+//! run of characters (including `/`), `?` matches any one character and
+//! `[a-z]` matches one character in a set. Numbers may have a duration suffix
+//! (`s`, `m`, `h`, `d` or `w`), which makes them a number of seconds. A fact
+//! on its own is true if it's true, a non-zero count, or non-empty text.
+//! Conditions combine with `!`, `&&`, `||` and parentheses, in order of
+//! decreasing precedence.
+//!
+//! A missing fact, or a value of a different type, is never equal to
+//! anything.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use crate::config::parse_duration;
+use crate::pattern::Glob;
+use crate::repo::{Fact, FactValue, Repo};
+
+/// A parsed `--where` expression.
+#[derive(Clone, Debug)]
+pub struct Query {
+    expr: Expr,
+}
+
+#[derive(Clone, Debug)]
+enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(Term, Op, Term),
+    Term(Term),
+}
+
+#[derive(Clone, Debug)]
+enum Term {
+    Fact(Fact),
+    Literal(FactValue),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Match,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(u64),
+    Text(String),
+    Op(Op),
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+}
+
+impl Query {
+    /// Parses an expression, or describes what's wrong with it.
+    pub fn parse(source: &str) -> Result<Query, String> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            pos: 0,
+        };
+        let expr = parser.parse_or()?;
+        if parser.pos < parser.tokens.len() {
+            return Err(
+                "unexpected input after the end of the expression".to_string()
+            );
+        }
+        Ok(Query {
+            expr,
+        })
+    }
+
+    /// Returns `true` if the repo satisfies the expression. Facts are only
+    /// worked out as they're needed, and repos that can't be opened never
+    /// satisfy it.
+    pub fn matches(&self, repo: &Repo) -> bool {
+        let Ok(git2_repo) = git2::Repository::open(repo.path()) else {
+            return false;
+        };
+        let mut facts = Facts {
+            repo,
+            git2_repo,
+            values: HashMap::new(),
+        };
+        self.expr.eval(&mut facts)
+    }
+}
+
+/// The facts about one repo, worked out on demand.
+struct Facts<'a> {
+    repo: &'a Repo,
+    git2_repo: git2::Repository,
+    values: HashMap<Fact, FactValue>,
+}
+
+impl Facts<'_> {
+    fn get(&mut self, fact: Fact) -> FactValue {
+        let Facts {
+            repo,
+            git2_repo,
+            values,
+        } = self;
+        values
+            .entry(fact)
+            .or_insert_with(|| repo.fact(git2_repo, fact))
+            .clone()
+    }
+}
+
+impl Expr {
+    fn eval(&self, facts: &mut Facts) -> bool {
+        match self {
+            Expr::Or(a, b) => a.eval(facts) || b.eval(facts),
+            Expr::And(a, b) => a.eval(facts) && b.eval(facts),
+            Expr::Not(expr) => !expr.eval(facts),
+            Expr::Compare(left, op, right) => {
+                compare(&left.value(facts), *op, &right.value(facts))
+            }
+            Expr::Term(term) => match term.value(facts) {
+                FactValue::Bool(b) => b,
+                FactValue::Number(n) => n != 0,
+                FactValue::Text(text) => !text.is_empty(),
+                FactValue::Missing => false,
+            },
+        }
+    }
+}
+
+impl Term {
+    fn value(&self, facts: &mut Facts) -> FactValue {
+        match self {
+            Term::Fact(fact) => facts.get(*fact),
+            Term::Literal(value) => value.clone(),
+        }
+    }
+}
+
+fn compare(left: &FactValue, op: Op, right: &FactValue) -> bool {
+    use FactValue::*;
+    if op == Op::Match {
+        return matches!(
+            (left, right),
+            (Text(text), Text(pattern)) if Glob::text(pattern).is_match(text)
+        );
+    }
+    let ordering = match (left, right) {
+        (Bool(a), Bool(b)) => a.cmp(b),
+        (Number(a), Number(b)) => a.cmp(b),
+        (Text(a), Text(b)) => a.cmp(b),
+        _ => return op == Op::Ne,
+    };
+    match op {
+        Op::Eq => ordering == Ordering::Equal,
+        Op::Ne => ordering != Ordering::Equal,
+        Op::Lt => ordering == Ordering::Less,
+        Op::Le => ordering != Ordering::Greater,
+        Op::Gt => ordering == Ordering::Greater,
+        Op::Ge => ordering != Ordering::Less,
+        Op::Match => unreachable!(),
+    }
+}
+
+/// Splits an expression into tokens.
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let (token, len) = match (chars[i], chars.get(i + 1)) {
+            (c, _) if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            ('&', Some('&')) => (Token::And, 2),
+            ('|', Some('|')) => (Token::Or, 2),
+            ('=', Some('=')) => (Token::Op(Op::Eq), 2),
+            ('!', Some('=')) => (Token::Op(Op::Ne), 2),
+            ('<', Some('=')) => (Token::Op(Op::Le), 2),
+            ('>', Some('=')) => (Token::Op(Op::Ge), 2),
+            ('<', _) => (Token::Op(Op::Lt), 1),
+            ('>', _) => (Token::Op(Op::Gt), 1),
+            ('~', _) => (Token::Op(Op::Match), 1),
+            ('!', _) => (Token::Not, 1),
+            ('(', _) => (Token::LParen, 1),
+            (')', _) => (Token::RParen, 1),
+            (quote @ ('\'' | '"'), _) => {
+                let len = chars[i + 1..]
+                    .iter()
+                    .position(|&c| c == quote)
+                    .ok_or("unterminated string")?;
+                let text = chars[i + 1..i + 1 + len].iter().collect();
+                (Token::Text(text), len + 2)
+            }
+            (c, _) if c.is_ascii_digit() => {
+                let word: String = chars[i..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_alphanumeric())
+                    .collect();
+                let seconds = parse_duration(&word)
+                    .ok_or_else(|| format!("invalid number `{}`", word))?;
+                let len = word.len();
+                (Token::Number(seconds.as_secs()), len)
+            }
+            (c, _) if c.is_ascii_alphabetic() || c == '_' => {
+                let word: String = chars[i..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+                    .collect();
+                let len = word.len();
+                (Token::Ident(word), len)
+            }
+            (c, _) => return Err(format!("unexpected `{}`", c)),
+        };
+        tokens.push(token);
+        i += len;
+    }
+    Ok(tokens)
+}
+
+/// A recursive-descent parser over a list of tokens.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    /// Consumes the next token if it's the given one.
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.tokens.get(self.pos) == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_and()?;
+        while self.eat(&Token::Or) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_not()?;
+        while self.eat(&Token::And) {
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_not()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, String> {
+        if self.eat(&Token::Not) {
+            return Ok(Expr::Not(Box::new(self.parse_not()?)));
+        }
+        if self.eat(&Token::LParen) {
+            let expr = self.parse_or()?;
+            if !self.eat(&Token::RParen) {
+                return Err("missing `)`".to_string());
+            }
+            return Ok(expr);
+        }
+        let left = self.parse_term()?;
+        let Some(Token::Op(op)) = self.tokens.get(self.pos).cloned() else {
+            return Ok(Expr::Term(left));
+        };
+        self.pos += 1;
+        let right = self.parse_term()?;
+        if op == Op::Match
+            && !matches!(right, Term::Literal(FactValue::Text(_)))
+        {
+            return Err("`~` needs a quoted pattern on its right".to_string());
+        }
+        Ok(Expr::Compare(left, op, right))
+    }
+
+    fn parse_term(&mut self) -> Result<Term, String> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        match token {
+            Some(Token::Ident(name)) => match name.as_str() {
+                "true" => Ok(Term::Literal(FactValue::Bool(true))),
+                "false" => Ok(Term::Literal(FactValue::Bool(false))),
+                _ => Fact::from_name(&name).map(Term::Fact).ok_or_else(|| {
+                    let names: Vec<&str> =
+                        Fact::ALL.iter().map(|fact| fact.name()).collect();
+                    format!(
+                        "unknown fact `{}` (known facts: {})",
+                        name,
+                        names.join(", ")
+                    )
+                }),
+            },
+            Some(Token::Number(n)) => Ok(Term::Literal(FactValue::Number(n))),
+            Some(Token::Text(text)) => Ok(Term::Literal(FactValue::Text(text))),
+            Some(_) => Err("expected a fact or a value".to_string()),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestEnv;
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |source| Query::parse(source).unwrap_err();
+        assert!(error("dirty &&").contains("end of expression"));
+        assert!(error("(dirty").contains("missing `)`"));
+        assert!(error("dirt").starts_with("unknown fact `dirt`"));
+        assert!(error("age > 3y").contains("invalid number `3y`"));
+        assert!(error("branch ~ remote").contains("quoted pattern"));
+        assert!(error("'main").contains("unterminated"));
+        assert!(error("dirty staged").contains("after the end"));
+    }
+
+    #[test]
+    fn test_matches() {
+        let mut env = TestEnv::new();
+        env.create_repo("repo")
+            .commit("file.txt", "content")
+            .stage("staged.txt", "new")
+            .build();
+        let repo = Repo::new(env.tempdir.path().join("repo"));
+        let holds = |source| Query::parse(source).unwrap().matches(&repo);
+        assert!(holds("dirty && staged && !unstaged && !untracked"));
+        assert!(holds("!has_remote && !remote && ahead == 0"));
+        assert!(holds("branch == 'master' && branch ~ 'mas*'"));
+        assert!(holds("age < 1h && !(age > 1h)"));
+        assert!(holds("stashes == 0 || false"));
+        assert!(holds("remote != 'x'"));
+        assert!(!holds("remote == 'x' || behind > 0"));
+    }
+}
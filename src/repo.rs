@@ -1,33 +1,223 @@
 //! Git repository representation for git-global.
 
 use std::fmt;
-use std::path::PathBuf;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+
+/// The kind of a git repository, which determines where its git directory
+/// lives and whether it has a working tree.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RepoKind {
+    /// A working tree with a `.git` directory.
+    Normal,
+    /// A linked working tree (from `git worktree add`), whose `.git` file
+    /// points into another repo's git directory.
+    Worktree,
+    /// A working tree whose `.git` file points to a separate git directory,
+    /// as with `git init --separate-git-dir`.
+    GitFile,
+    /// A bare repository, with no working tree.
+    Bare,
+}
+
+impl RepoKind {
+    /// Works out what kind of repo lives at the given path from its `.git`
+    /// entry, without opening it. Without a `.git` entry, the path may be a
+    /// bare repo.
+    pub fn detect(path: &Path) -> Option<RepoKind> {
+        let dot_git = path.join(".git");
+        match fs::metadata(&dot_git) {
+            Ok(md) if md.is_dir() => Some(RepoKind::Normal),
+            Ok(_) => {
+                let contents = fs::read_to_string(&dot_git).ok()?;
+                let gitdir = contents.strip_prefix("gitdir:")?.trim();
+                // Linked worktrees' git directories point back to the main
+                // repo's with a `commondir` file.
+                if path.join(gitdir).join("commondir").is_file() {
+                    Some(RepoKind::Worktree)
+                } else {
+                    Some(RepoKind::GitFile)
+                }
+            }
+            // The git directory of a `--separate-git-dir` checkout isn't
+            // bare, since it names its working tree in `core.worktree`.
+            Err(_) => git2::Repository::open_ext(
+                path,
+                git2::RepositoryOpenFlags::NO_SEARCH,
+                &[] as &[&std::ffi::OsStr],
+            )
+            .ok()
+            .filter(|repo| repo.is_bare())
+            .map(|_| RepoKind::Bare),
+        }
+    }
+
+    /// Returns `true` if this kind of repo has a working tree.
+    pub fn has_work_tree(&self) -> bool {
+        *self != RepoKind::Bare
+    }
+}
+
+impl fmt::Display for RepoKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RepoKind::Normal => write!(f, "normal"),
+            RepoKind::Worktree => write!(f, "worktree"),
+            RepoKind::GitFile => write!(f, "gitfile"),
+            RepoKind::Bare => write!(f, "bare"),
+        }
+    }
+}
+
+/// A fact about the state of a repo, by which repos can be selected (see the
+/// `query` module).
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Fact {
+    /// Whether tracked files have staged or unstaged changes.
+    Dirty,
+    /// Whether the index has changes.
+    Staged,
+    /// Whether tracked files in the working tree have changes that aren't
+    /// staged.
+    Unstaged,
+    /// Whether the working tree has untracked files.
+    Untracked,
+    /// The number of commits on the current branch that aren't on its
+    /// upstream branch.
+    Ahead,
+    /// The number of commits on the current branch's upstream branch that
+    /// aren't on it.
+    Behind,
+    /// The number of stashes.
+    Stashes,
+    /// The name of the current branch, unless HEAD is detached.
+    Branch,
+    /// The URL of the `origin` remote, or else of the first remote.
+    Remote,
+    /// Whether the repo has any remotes.
+    HasRemote,
+    /// How long ago the current commit was made, in seconds.
+    Age,
+}
+
+impl Fact {
+    /// Every fact, in the order they're documented.
+    pub const ALL: [Fact; 11] = [
+        Fact::Dirty,
+        Fact::Staged,
+        Fact::Unstaged,
+        Fact::Untracked,
+        Fact::Ahead,
+        Fact::Behind,
+        Fact::Stashes,
+        Fact::Branch,
+        Fact::Remote,
+        Fact::HasRemote,
+        Fact::Age,
+    ];
+
+    /// Returns the fact with the given name, if there is one.
+    pub fn from_name(name: &str) -> Option<Fact> {
+        Fact::ALL.into_iter().find(|fact| fact.name() == name)
+    }
+
+    /// Returns the name of this fact.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Fact::Dirty => "dirty",
+            Fact::Staged => "staged",
+            Fact::Unstaged => "unstaged",
+            Fact::Untracked => "untracked",
+            Fact::Ahead => "ahead",
+            Fact::Behind => "behind",
+            Fact::Stashes => "stashes",
+            Fact::Branch => "branch",
+            Fact::Remote => "remote",
+            Fact::HasRemote => "has_remote",
+            Fact::Age => "age",
+        }
+    }
+}
+
+/// The value of a `Fact` for a particular repo.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FactValue {
+    Bool(bool),
+    Number(u64),
+    Text(String),
+    /// The fact doesn't apply, e.g., the branch name of a detached HEAD.
+    Missing,
+}
+
+/// A remote's name, with how many of its remote-tracking refs a fetch
+/// updated, or why the fetch failed.
+pub type RemoteFetch = (String, std::result::Result<usize, git2::Error>);
+
+/// How a local branch compares to its upstream branch.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct Tracking {
+    pub branch: String,
+    pub upstream: String,
+    /// The number of commits on the branch that aren't on its upstream.
+    pub ahead: usize,
+    /// The number of commits on the upstream that aren't on the branch.
+    pub behind: usize,
+}
+
+impl fmt::Display for Tracking {
+    /// Formats the comparison like `git status --branch` does, e.g.,
+    /// `main...origin/main [ahead 2, behind 1]`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}...{}", self.branch, self.upstream)?;
+        match (self.ahead, self.behind) {
+            (0, 0) => Ok(()),
+            (ahead, 0) => write!(f, " [ahead {}]", ahead),
+            (0, behind) => write!(f, " [behind {}]", behind),
+            (ahead, behind) => {
+                write!(f, " [ahead {}, behind {}]", ahead, behind)
+            }
+        }
+    }
+}
 
 /// A git repository, represented by the full path to its base directory.
-#[derive(Clone, Eq, Hash, PartialEq, Serialize)]
+///
+/// That's the working tree for most repos, or the git directory itself for
+/// bare repos. Repos are compared by path alone.
+#[derive(Clone, Serialize)]
 pub struct Repo {
     path: PathBuf,
+    kind: RepoKind,
 }
 
 impl Repo {
     pub fn new<P: Into<PathBuf>>(path: P) -> Repo {
+        Repo::with_kind(path, RepoKind::Normal)
+    }
+
+    /// Create a new `Repo` of the given kind.
+    pub fn with_kind<P: Into<PathBuf>>(path: P, kind: RepoKind) -> Repo {
         Repo {
             path: path.into(),
+            kind,
         }
     }
 
-    /// Returns the `git2::Repository` equivalent of this repo.
-    pub fn as_git2_repo(&self) -> ::git2::Repository {
-        ::git2::Repository::open(&self.path).unwrap_or_else(|e| {
-            panic!(
-                "Could not open {} as a git repo: {:?}. Perhaps you should run \
-                   `git global scan` again.",
-                self.path.as_path().to_str().unwrap(),
-                e
-            )
-        })
+    /// Returns the kind of repo this is.
+    pub fn kind(&self) -> RepoKind {
+        self.kind
+    }
+
+    /// Returns the `git2::Repository` equivalent of this repo, or why it
+    /// can't be opened, e.g., because it's gone since it was cached.
+    pub fn as_git2_repo(
+        &self,
+    ) -> std::result::Result<::git2::Repository, git2::Error> {
+        ::git2::Repository::open(&self.path)
     }
 
     /// Returns the full path to the repo as a `String`.
@@ -39,12 +229,10 @@ impl Repo {
     pub fn get_status_lines(
         &self,
         mut status_opts: ::git2::StatusOptions,
-    ) -> Vec<String> {
-        let git2_repo = self.as_git2_repo();
-        let statuses = git2_repo
-            .statuses(Some(&mut status_opts))
-            .unwrap_or_else(|_| panic!("Could not get statuses for {}.", self));
-        statuses
+    ) -> std::result::Result<Vec<String>, git2::Error> {
+        let git2_repo = self.as_git2_repo()?;
+        let statuses = git2_repo.statuses(Some(&mut status_opts))?;
+        Ok(statuses
             .iter()
             .map(|entry| {
                 let path = entry.path().unwrap();
@@ -52,64 +240,200 @@ impl Repo {
                 let status_for_path = get_short_format_status(status);
                 format!("{} {}", status_for_path, path)
             })
-            .collect()
+            .collect())
     }
 
-    /// Transforms a git2::Branch into a git2::Commit
-    fn branch_to_commit(branch: git2::Branch) -> Option<git2::Commit> {
-        branch.into_reference().peel_to_commit().ok()
+    /// Works out a fact about the state of the repo, which `git2_repo` must
+    /// be opened from.
+    pub fn fact(
+        &self,
+        git2_repo: &mut git2::Repository,
+        fact: Fact,
+    ) -> FactValue {
+        match fact {
+            Fact::Dirty => self.has_status(git2_repo, |status| {
+                !status.is_wt_new() && !status.is_ignored()
+            }),
+            Fact::Staged => self.has_status(git2_repo, |status| {
+                status.intersects(
+                    git2::Status::INDEX_NEW
+                        | git2::Status::INDEX_MODIFIED
+                        | git2::Status::INDEX_DELETED
+                        | git2::Status::INDEX_RENAMED
+                        | git2::Status::INDEX_TYPECHANGE,
+                )
+            }),
+            Fact::Unstaged => self.has_status(git2_repo, |status| {
+                status.intersects(
+                    git2::Status::WT_MODIFIED
+                        | git2::Status::WT_DELETED
+                        | git2::Status::WT_RENAMED
+                        | git2::Status::WT_TYPECHANGE,
+                )
+            }),
+            Fact::Untracked => {
+                self.has_status(git2_repo, |status| status.is_wt_new())
+            }
+            Fact::Ahead | Fact::Behind => {
+                let (ahead, behind) =
+                    head_ahead_behind(git2_repo).unwrap_or((0, 0));
+                let count = if fact == Fact::Ahead { ahead } else { behind };
+                FactValue::Number(count as u64)
+            }
+            Fact::Stashes => {
+                let mut n = 0;
+                let _ = git2_repo.stash_foreach(|_, _, _| {
+                    n += 1;
+                    true
+                });
+                FactValue::Number(n)
+            }
+            Fact::Branch => match head_branch(git2_repo) {
+                Some(branch) => FactValue::Text(branch),
+                None => FactValue::Missing,
+            },
+            Fact::Remote => match remote_url(git2_repo) {
+                Some(url) => FactValue::Text(url),
+                None => FactValue::Missing,
+            },
+            Fact::HasRemote => FactValue::Bool(
+                git2_repo.remotes().is_ok_and(|names| !names.is_empty()),
+            ),
+            Fact::Age => {
+                let Ok(commit) =
+                    git2_repo.head().and_then(|head| head.peel_to_commit())
+                else {
+                    return FactValue::Missing;
+                };
+                let now = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|d| d.as_secs() as i64)
+                    .unwrap_or(0);
+                FactValue::Number((now - commit.time().seconds()).max(0) as u64)
+            }
+        }
     }
 
-    /// Walks through revisions, returning all ancestor Oids of a Commit
-    fn get_log(
-        repo: &git2::Repository,
-        commit: git2::Commit,
-    ) -> Vec<git2::Oid> {
-        let mut revwalk = repo.revwalk().unwrap();
-        revwalk.push(commit.id()).unwrap();
-        revwalk.filter_map(|id| id.ok()).collect::<Vec<git2::Oid>>()
+    /// Returns whether any file's status passes `pred`, considering
+    /// untracked files only if `pred` might. Bare repos have no changes.
+    fn has_status<P>(&self, git2_repo: &git2::Repository, pred: P) -> FactValue
+    where
+        P: Fn(git2::Status) -> bool,
+    {
+        if !self.kind.has_work_tree() {
+            return FactValue::Bool(false);
+        }
+        let mut opts = git2::StatusOptions::new();
+        opts.include_untracked(pred(git2::Status::WT_NEW))
+            .include_ignored(false);
+        let changed =
+            git2_repo.statuses(Some(&mut opts)).is_ok_and(|statuses| {
+                statuses.iter().any(|entry| pred(entry.status()))
+            });
+        FactValue::Bool(changed)
     }
 
-    /// Returns true if commits of local branches are ahead of those on remote branches
+    /// Returns true if any local branch has commits that aren't on its
+    /// upstream branch.
     pub fn is_ahead(&self) -> bool {
-        let repo = self.as_git2_repo();
-        let local_branches = match repo.branches(Some(git2::BranchType::Local))
-        {
-            Ok(branches) => branches,
-            Err(_) => return false,
-        };
-        let remote_branches =
-            match repo.branches(Some(git2::BranchType::Remote)) {
-                Ok(branches) => branches,
-                Err(_) => return false,
-            };
-
-        let remote_commit_ids = remote_branches
-            .filter_map(|branch| branch.ok().map(|b| b.0))
-            .filter_map(Self::branch_to_commit)
-            .flat_map(|commit| Self::get_log(&repo, commit))
-            .collect::<Vec<_>>();
-
-        #[allow(clippy::let_and_return)]
-        let is_ahead = local_branches
-            .filter_map(|branch| branch.ok().map(|b| b.0))
-            .any(|branch| match Self::branch_to_commit(branch) {
-                Some(commit) => !remote_commit_ids.contains(&commit.id()),
-                None => false,
-            });
-        is_ahead
+        self.get_tracking_list()
+            .is_ok_and(|list| list.iter().any(|branch| branch.ahead > 0))
+    }
+
+    /// Returns how each local branch with an upstream branch compares to it,
+    /// sorted by branch name. Branches without an upstream are left out.
+    pub fn get_tracking_list(
+        &self,
+    ) -> std::result::Result<Vec<Tracking>, git2::Error> {
+        let repo = self.as_git2_repo()?;
+        let branches = repo.branches(Some(git2::BranchType::Local))?;
+        let mut list: Vec<Tracking> = branches
+            .filter_map(|branch| branch.ok())
+            .filter_map(|(branch, _)| tracking(&repo, &branch))
+            .collect();
+        list.sort_by(|a, b| a.branch.cmp(&b.branch));
+        Ok(list)
+    }
+
+    /// Fetches every remote, returning each remote's name along with how many
+    /// of its remote-tracking refs were updated, or why the fetch failed.
+    /// With `prune`, refs whose branches are gone from a remote are removed,
+    /// too; otherwise, `fetch.prune` decides, as with `git fetch`.
+    ///
+    /// Fails if the repo or its list of remotes can't be read at all.
+    pub fn fetch(
+        &self,
+        prune: bool,
+    ) -> std::result::Result<Vec<RemoteFetch>, git2::Error> {
+        let repo = git2::Repository::open(&self.path)?;
+        let names = repo.remotes()?;
+        Ok(names
+            .iter()
+            .filter_map(|name| name.ok().flatten())
+            .map(|name| (name.to_string(), fetch_remote(&repo, name, prune)))
+            .collect())
+    }
+
+    /// Pushes a local branch to its upstream branch, which must be a
+    /// fast-forward; the remote's branch is never overwritten.
+    pub fn push(&self, branch: &str) -> std::result::Result<(), git2::Error> {
+        let repo = git2::Repository::open(&self.path)?;
+        let local = format!("refs/heads/{}", branch);
+        let remote_name = repo.branch_upstream_remote(&local)?;
+        let remote_name = remote_name.as_str().map_err(|_| {
+            git2::Error::from_str("the remote's name is not valid UTF-8")
+        })?;
+        let merge = repo.branch_upstream_merge(&local)?;
+        let merge = merge.as_str().map_err(|_| {
+            git2::Error::from_str("the upstream's name is not valid UTF-8")
+        })?;
+        let mut remote = repo.find_remote(remote_name)?;
+        let mut rejection = None;
+        let mut callbacks = git2::RemoteCallbacks::new();
+        callbacks.credentials(credentials()).push_update_reference(
+            |_, status| {
+                rejection = status.map(String::from);
+                Ok(())
+            },
+        );
+        let mut opts = git2::PushOptions::new();
+        opts.remote_callbacks(callbacks);
+        // Without a leading `+`, the push fails unless it's a fast-forward.
+        remote.push(&[format!("{}:{}", local, merge)], Some(&mut opts))?;
+        drop(opts);
+        match rejection {
+            Some(reason) => Err(git2::Error::from_str(&format!(
+                "the remote rejected it: {}",
+                reason
+            ))),
+            None => Ok(()),
+        }
     }
 
     /// Returns the list of stash entries for the repo.
-    pub fn get_stash_list(&self) -> Vec<String> {
+    pub fn get_stash_list(
+        &self,
+    ) -> std::result::Result<Vec<String>, git2::Error> {
         let mut stash = vec![];
-        self.as_git2_repo()
-            .stash_foreach(|index, name, _oid| {
-                stash.push(format!("stash@{{{}}}: {}", index, name));
-                true
-            })
-            .unwrap();
-        stash
+        self.as_git2_repo()?.stash_foreach(|index, name, _oid| {
+            stash.push(format!("stash@{{{}}}: {}", index, name));
+            true
+        })?;
+        Ok(stash)
+    }
+}
+
+impl PartialEq for Repo {
+    fn eq(&self, other: &Repo) -> bool {
+        self.path == other.path
+    }
+}
+
+impl Eq for Repo {}
+
+impl Hash for Repo {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.path.hash(state);
     }
 }
 
@@ -119,6 +443,115 @@ impl fmt::Display for Repo {
     }
 }
 
+/// Returns the name of the branch HEAD points to, even if it has no commits
+/// yet, or `None` if HEAD is detached.
+fn head_branch(repo: &git2::Repository) -> Option<String> {
+    let head = repo.find_reference("HEAD").ok()?;
+    let target = head.symbolic_target().ok()??;
+    target.strip_prefix("refs/heads/").map(String::from)
+}
+
+/// Returns how many commits the current branch is ahead of and behind its
+/// upstream branch, if it has one.
+fn head_ahead_behind(repo: &git2::Repository) -> Option<(usize, usize)> {
+    let head = repo.head().ok()?;
+    if !head.is_branch() {
+        return None;
+    }
+    let branch = git2::Branch::wrap(head);
+    let upstream = branch.upstream().ok()?;
+    upstream_ahead_behind(repo, &branch, &upstream)
+}
+
+/// Returns how a local branch compares to its upstream branch, if it has one.
+pub(crate) fn tracking(
+    repo: &git2::Repository,
+    branch: &git2::Branch,
+) -> Option<Tracking> {
+    let upstream = branch.upstream().ok()?;
+    let (ahead, behind) = upstream_ahead_behind(repo, branch, &upstream)?;
+    Some(Tracking {
+        branch: branch.name().ok()??.to_string(),
+        upstream: upstream.name().ok()??.to_string(),
+        ahead,
+        behind,
+    })
+}
+
+/// Returns how many commits a branch is ahead of and behind its upstream.
+fn upstream_ahead_behind(
+    repo: &git2::Repository,
+    branch: &git2::Branch,
+    upstream: &git2::Branch,
+) -> Option<(usize, usize)> {
+    let local = branch.get().target()?;
+    let remote = upstream.get().target()?;
+    repo.graph_ahead_behind(local, remote).ok()
+}
+
+/// Fetches a remote with its configured refspecs, returning how many
+/// remote-tracking refs were updated.
+fn fetch_remote(
+    repo: &git2::Repository,
+    name: &str,
+    prune: bool,
+) -> std::result::Result<usize, git2::Error> {
+    let mut remote = repo.find_remote(name)?;
+    let mut updated = 0;
+    let mut callbacks = git2::RemoteCallbacks::new();
+    callbacks.credentials(credentials()).update_tips(|_, _, _| {
+        updated += 1;
+        true
+    });
+    let mut opts = git2::FetchOptions::new();
+    opts.remote_callbacks(callbacks);
+    if prune {
+        opts.prune(git2::FetchPrune::On);
+    }
+    remote.fetch(&[] as &[&str], Some(&mut opts), None)?;
+    drop(opts);
+    Ok(updated)
+}
+
+/// Returns a callback that supplies credentials for a remote without ever
+/// prompting: the SSH agent's keys or the platform's default credentials,
+/// each tried once.
+fn credentials() -> impl FnMut(
+    &str,
+    Option<&str>,
+    git2::CredentialType,
+) -> std::result::Result<git2::Cred, git2::Error> {
+    let mut tried = git2::CredentialType::empty();
+    move |_, username, allowed| {
+        let untried = allowed - tried;
+        if untried.contains(git2::CredentialType::USERNAME) {
+            tried |= git2::CredentialType::USERNAME;
+            return git2::Cred::username(username.unwrap_or("git"));
+        }
+        if untried.contains(git2::CredentialType::SSH_KEY) {
+            tried |= git2::CredentialType::SSH_KEY;
+            return git2::Cred::ssh_key_from_agent(username.unwrap_or("git"));
+        }
+        if untried.contains(git2::CredentialType::DEFAULT) {
+            tried |= git2::CredentialType::DEFAULT;
+            return git2::Cred::default();
+        }
+        Err(git2::Error::from_str("no usable credentials were found"))
+    }
+}
+
+/// Returns the URL of the `origin` remote, or else of the first remote.
+fn remote_url(repo: &git2::Repository) -> Option<String> {
+    let names = repo.remotes().ok()?;
+    let name = names
+        .iter()
+        .filter_map(|name| name.ok().flatten())
+        .find(|name| *name == "origin")
+        .or_else(|| names.iter().next()?.ok().flatten())?;
+    let remote = repo.find_remote(name).ok()?;
+    remote.url().ok().map(String::from)
+}
+
 /// Translates a file's status flags to their "short format" representation.
 ///
 /// Follows an example in the git2-rs crate's `examples/status.rs`.
@@ -183,6 +616,40 @@ mod tests {
         assert!(repo.is_ahead());
     }
 
+    #[test]
+    fn test_get_tracking_list() {
+        let mut env = TestEnv::new();
+        let repo = Repo::new(env.tempdir.path().join("repo_tracking"));
+
+        env.create_repo("repo_tracking")
+            .commit("file.txt", "content")
+            .setup_remote()
+            .commit("file.txt", "more content")
+            .commit("file.txt", "even more content")
+            .build();
+        // A branch without an upstream isn't compared to anything.
+        let git2_repo = repo.as_git2_repo().unwrap();
+        let head = git2_repo.head().unwrap().peel_to_commit().unwrap();
+        git2_repo.branch("local-only", &head, false).unwrap();
+
+        let tracking = repo.get_tracking_list().unwrap();
+        assert_eq!(tracking.len(), 1);
+        assert_eq!(tracking[0].branch, "master");
+        assert_eq!(tracking[0].upstream, "origin/master");
+        assert_eq!((tracking[0].ahead, tracking[0].behind), (2, 0));
+        assert_eq!(tracking[0].to_string(), "master...origin/master [ahead 2]");
+
+        let diverged = Tracking {
+            ahead: 1,
+            behind: 3,
+            ..tracking[0].clone()
+        };
+        assert_eq!(
+            diverged.to_string(),
+            "master...origin/master [ahead 1, behind 3]"
+        );
+    }
+
     #[test]
     fn test_get_status_lines() {
         let mut env = TestEnv::new();
@@ -197,7 +664,7 @@ mod tests {
 
         let mut opts = git2::StatusOptions::new();
         opts.include_untracked(true);
-        let lines = repo.get_status_lines(opts);
+        let lines = repo.get_status_lines(opts).unwrap();
 
         assert!(lines.iter().any(|l| l.starts_with("A  staged.txt")));
         assert!(lines.iter().any(|l| l.starts_with("?? unstaged.txt")));
@@ -217,7 +684,7 @@ mod tests {
             .stash("stash 2")
             .build();
 
-        let stashes = repo.get_stash_list();
+        let stashes = repo.get_stash_list().unwrap();
         assert_eq!(stashes.len(), 2);
         // Stashes are usually LIFO, so stash 2 is at index 0
         assert!(stashes[0].contains("stash 2"));
@@ -3,18 +3,22 @@
 use std::collections::HashMap;
 use std::io::Write;
 
-use serde_json::json;
+use serde_json::{Map, Value, json};
 
 use crate::repo::Repo;
 
 /// A report containing the results of a git-global subcommand.
 ///
-/// Contains overall messages and per-repo messages.
+/// Contains overall messages and per-repo messages, plus any structured data
+/// that only appears in JSON output.
 pub struct Report {
     messages: Vec<String>,
     repo_messages: HashMap<Repo, Vec<String>>,
     repos: Vec<Repo>,
     pad_repo_output: bool,
+    repo_headers: bool,
+    data: Map<String, Value>,
+    failed: bool,
 }
 
 impl Report {
@@ -29,6 +33,9 @@ impl Report {
             repos: repos.to_owned(),
             repo_messages,
             pad_repo_output: false,
+            repo_headers: true,
+            data: Map::new(),
+            failed: false,
         }
     }
 
@@ -40,6 +47,14 @@ impl Report {
         self.pad_repo_output = true;
     }
 
+    /// Declares that per-repo messages name their repo themselves.
+    ///
+    /// Per-repo messages are then printed without the usual line with the
+    /// repo's path above them, one per line, still grouped by repo.
+    pub fn omit_repo_headers(&mut self) {
+        self.repo_headers = false;
+    }
+
     /// Adds a message that applies to the overall operation.
     pub fn add_message(&mut self, message: String) {
         self.messages.push(message);
@@ -52,6 +67,44 @@ impl Report {
         }
     }
 
+    /// Adds a message saying why the given repo couldn't be read, and marks
+    /// the report failed.
+    pub fn add_repo_error(&mut self, repo: &Repo, error: &git2::Error) {
+        let mut message = format!("Could not read repo: {}", error.message());
+        if error.code() == git2::ErrorCode::NotFound {
+            message.push_str(" (Run `git global prune` to forget it.)");
+        }
+        self.add_repo_message(repo, message);
+        self.mark_failed();
+    }
+
+    /// Adds a field to the JSON form of the report, alongside the messages.
+    pub fn add_data(&mut self, key: &str, value: Value) {
+        self.data.insert(key.to_string(), value);
+    }
+
+    /// Declares that the operation failed, even though it produced a report,
+    /// e.g., because a command it ran failed. The process then exits with a
+    /// nonzero status.
+    pub fn mark_failed(&mut self) {
+        self.failed = true;
+    }
+
+    /// Returns `true` if the operation was declared to have failed.
+    pub fn is_failed(&self) -> bool {
+        self.failed
+    }
+
+    /// Writes all result messages to the given writer, as JSON if `json` is
+    /// set, or else as text.
+    pub fn write<W: Write>(&self, writer: &mut W, json: bool) {
+        if json {
+            self.print_json(writer);
+        } else {
+            self.print(writer);
+        }
+    }
+
     /// Writes all result messages to the given writer, as text.
     pub fn print<W: Write>(&self, writer: &mut W) {
         for msg in self.messages.iter() {
@@ -60,7 +113,9 @@ impl Report {
         for repo in self.repos.iter() {
             let messages = self.repo_messages.get(repo).unwrap();
             if !messages.is_empty() {
-                writeln!(writer, "{}", repo).unwrap();
+                if self.repo_headers {
+                    writeln!(writer, "{}", repo).unwrap();
+                }
                 for line in messages.iter().filter(|l| !l.is_empty()) {
                     writeln!(writer, "{}", line).unwrap();
                 }
@@ -78,11 +133,14 @@ impl Report {
             let msgs = messages.iter().filter(|l| !l.is_empty());
             repo_messages.insert(repo.path(), msgs.collect());
         }
-        let json = json!({
+        let mut json = json!({
             "error": false,
             "messages": self.messages,
             "repo_messages": repo_messages
         });
+        if let Value::Object(ref mut fields) = json {
+            fields.extend(self.data.clone());
+        }
         writeln!(writer, "{:#}", json).unwrap();
     }
 }
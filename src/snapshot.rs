@@ -0,0 +1,247 @@
+//! Directory snapshots for incremental scans.
+//!
+//! A `Snapshot` remembers the modification time and the interesting entries
+//! (subdirectories, symbolic links, and the `.git` and `HEAD` entries that
+//! mark repos) of every directory read during a scan. A directory's
+//! modification time changes whenever an entry is added to, removed from, or
+//! renamed within it, so an incremental scan only needs to re-read the
+//! directories whose modification times differ from the snapshot; every other
+//! listing is served from the snapshot.
+//!
+//! Like the cache, the snapshot is written to a temporary file that then
+//! replaces it, so a scan never reads a partly written snapshot.
+
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use tempfile::NamedTempFile;
+
+use crate::walk::{self, Child, FileKind, ReadDir};
+
+/// Names of files that can mark a repo.
+const MARKERS: [&str; 2] = [".git", "HEAD"];
+
+/// Directories modified this close to the start of a scan aren't recorded,
+/// since a change within the same timestamp tick would go unnoticed.
+const RACY_WINDOW: Duration = Duration::from_secs(2);
+
+/// The name of a recorded entry. Names that are valid UTF-8 are stored as
+/// plain strings; any others are stored in the platform's own form, so that
+/// a listing served from the snapshot has every entry that reading the
+/// directory would (and a scan reports the same non-UTF-8 paths).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+enum Name {
+    Utf8(String),
+    Other(OsString),
+}
+
+impl From<&OsStr> for Name {
+    fn from(name: &OsStr) -> Name {
+        match name.to_str() {
+            Some(name) => Name::Utf8(name.to_string()),
+            None => Name::Other(name.to_os_string()),
+        }
+    }
+}
+
+impl From<&Name> for OsString {
+    fn from(name: &Name) -> OsString {
+        match name {
+            Name::Utf8(name) => name.into(),
+            Name::Other(name) => name.clone(),
+        }
+    }
+}
+
+/// The recorded state of a single directory.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct DirState {
+    mtime: SystemTime,
+    entries: Vec<(Name, FileKind)>,
+}
+
+/// Directory listings recorded during a scan, keyed by directory path.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Snapshot {
+    dirs: HashMap<PathBuf, DirState>,
+}
+
+impl Snapshot {
+    /// Reads a snapshot from the given file, returning `None` if it doesn't
+    /// exist or can't be parsed.
+    pub fn load(path: &Path) -> Option<Snapshot> {
+        let f = File::open(path).ok()?;
+        serde_json::from_reader(BufReader::new(f)).ok()
+    }
+
+    /// Replaces the given file with the snapshot, atomically.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let dir = match path.parent() {
+            Some(parent) if parent != Path::new("") => parent,
+            _ => Path::new("."),
+        };
+        let mut temp = NamedTempFile::new_in(dir)?;
+        {
+            let mut writer = BufWriter::new(temp.as_file_mut());
+            serde_json::to_writer(&mut writer, self)?;
+            writer.flush()?;
+        }
+        temp.persist(path)?;
+        Ok(())
+    }
+}
+
+/// Returns `true` if a directory entry is worth remembering for repo
+/// discovery.
+fn is_interesting(child: &Child) -> bool {
+    child.kind != FileKind::File || MARKERS.iter().any(|m| child.name == *m)
+}
+
+/// A `ReadDir` that records every listing into a new `Snapshot`, optionally
+/// serving unchanged directories from a previous one.
+pub struct Recorder<'a> {
+    previous: Option<&'a Snapshot>,
+    current: Mutex<Snapshot>,
+    cutoff: SystemTime,
+    n_reused: AtomicUsize,
+}
+
+impl<'a> Recorder<'a> {
+    /// Create a new `Recorder`, reusing listings from `previous`, if given.
+    pub fn new(previous: Option<&'a Snapshot>) -> Recorder<'a> {
+        Recorder {
+            previous,
+            current: Mutex::new(Snapshot::default()),
+            cutoff: SystemTime::now() - RACY_WINDOW,
+            n_reused: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the number of directories that were listed from the previous
+    /// snapshot instead of being read.
+    pub fn n_reused(&self) -> usize {
+        self.n_reused.load(Ordering::Relaxed)
+    }
+
+    /// Consumes the recorder, returning the snapshot it recorded.
+    pub fn into_snapshot(self) -> Snapshot {
+        self.current.into_inner().unwrap()
+    }
+
+    /// Returns the previous listing of a directory, if it hasn't changed.
+    fn unchanged(&self, dir: &Path, mtime: SystemTime) -> Option<&DirState> {
+        self.previous?
+            .dirs
+            .get(dir)
+            .filter(|state| state.mtime == mtime)
+    }
+}
+
+impl ReadDir for Recorder<'_> {
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<Child>> {
+        let mtime = fs::metadata(dir)?.modified()?;
+        let state = match self.unchanged(dir, mtime) {
+            Some(state) => {
+                self.n_reused.fetch_add(1, Ordering::Relaxed);
+                state.clone()
+            }
+            None => {
+                let children = walk::read_dir(dir)?;
+                let state = DirState {
+                    mtime,
+                    entries: children
+                        .iter()
+                        .filter(|child| is_interesting(child))
+                        .map(|child| {
+                            (child.name.as_os_str().into(), child.kind)
+                        })
+                        .collect(),
+                };
+                if mtime < self.cutoff {
+                    self.current
+                        .lock()
+                        .unwrap()
+                        .dirs
+                        .insert(dir.to_path_buf(), state);
+                }
+                return Ok(children);
+            }
+        };
+        let children = state
+            .entries
+            .iter()
+            .map(|(name, kind)| Child {
+                name: name.into(),
+                kind: *kind,
+            })
+            .collect();
+        self.current
+            .lock()
+            .unwrap()
+            .dirs
+            .insert(dir.to_path_buf(), state);
+        Ok(children)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::create_dir_all;
+
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_unchanged_directories_are_reused() {
+        let tempdir = TempDir::new().unwrap();
+        let dir = tempdir.path().join("dir");
+        create_dir_all(dir.join("sub")).unwrap();
+        File::create(dir.join("file.txt")).unwrap();
+        let old = SystemTime::now() - Duration::from_secs(60);
+        File::open(&dir).unwrap().set_modified(old).unwrap();
+
+        let recorder = Recorder::new(None);
+        assert_eq!(recorder.read_dir(&dir).unwrap().len(), 2);
+        let snapshot = recorder.into_snapshot();
+        assert_eq!(snapshot.dirs.len(), 1);
+
+        // Only the interesting entries are listed from the snapshot.
+        let recorder = Recorder::new(Some(&snapshot));
+        let children = recorder.read_dir(&dir).unwrap();
+        assert_eq!(recorder.n_reused(), 1);
+        assert_eq!(children.len(), 1);
+        assert_eq!(children[0].name, "sub");
+
+        // Changing the directory forces it to be read again.
+        create_dir_all(dir.join("new")).unwrap();
+        let recorder = Recorder::new(Some(&snapshot));
+        assert_eq!(recorder.read_dir(&dir).unwrap().len(), 3);
+        assert_eq!(recorder.n_reused(), 0);
+    }
+
+    #[test]
+    fn test_save_replaces_snapshot() {
+        let tempdir = TempDir::new().unwrap();
+        let file = tempdir.path().join("dirs.json");
+        fs::write(&file, "not a snapshot").unwrap();
+        let recorder = Recorder::new(None);
+        recorder.read_dir(tempdir.path()).unwrap();
+        recorder.into_snapshot().save(&file).unwrap();
+
+        assert!(Snapshot::load(&file).is_some());
+        // Nothing is left behind but the snapshot.
+        let names: Vec<_> = fs::read_dir(tempdir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names, vec!["dirs.json"]);
+    }
+}
@@ -1,19 +1,34 @@
 //! Subcommand implementations and dispatch function `run()`.
+pub mod add;
 pub mod ahead;
+pub mod behind;
+pub mod exec;
+pub mod fetch;
+pub mod forget;
+pub mod git;
+pub mod grep;
 pub mod ignore;
 pub mod ignored;
 pub mod info;
 pub mod install_manpage;
 pub mod list;
+pub mod prune;
+pub mod pull;
+pub mod push;
 pub mod scan;
 pub mod staged;
 pub mod stashed;
 pub mod status;
+pub mod tag;
 pub mod unstaged;
+pub mod untag;
+pub mod watch;
 
 use std::path::PathBuf;
 
-use crate::config::Config;
+use clap::ArgMatches;
+
+use crate::config::{Config, ScanMode};
 use crate::errors::{GitGlobalError, Result};
 use crate::report::Report;
 
@@ -21,48 +36,157 @@ use crate::report::Report;
 ///
 /// If `None` is given for the optional subcommand, run `config.default_cmd`.
 /// Else, try to match the given `&str` to a known subcommand.
-/// The `args` parameter carries additional positional arguments for
-/// subcommands that accept them (e.g. `ignore`, `scan`).
+/// The `args` parameter carries the subcommand's own command-line arguments
+/// for subcommands that accept them (e.g. `ignore`, `scan`); arguments that
+/// weren't given (or don't apply) take their default values.
 pub fn run(
     maybe_subcmd: Option<&str>,
     config: Config,
-    args: Vec<String>,
+    args: &ArgMatches,
 ) -> Result<Report> {
     let command = maybe_subcmd.unwrap_or(&config.default_cmd);
     match command {
         "info" => info::execute(config),
         "list" => list::execute(config),
         "scan" => {
-            let paths = args.into_iter().map(PathBuf::from).collect();
-            scan::execute(config, paths)
+            let mode = if flag(args, "incremental") {
+                ScanMode::Incremental
+            } else {
+                ScanMode::Full
+            };
+            scan::execute(config, paths(args), mode)
         }
         "staged" => staged::execute(config),
         "stashed" => stashed::execute(config),
         "status" => status::execute(config),
         "unstaged" => unstaged::execute(config),
         "ahead" => ahead::execute(config),
+        "behind" => behind::execute(config),
+        "exec" => {
+            let mut options = exec::ExecOptions {
+                fail_fast: flag(args, "fail_fast"),
+                only_failed: flag(args, "only_failed"),
+                ..Default::default()
+            };
+            if let Ok(Some(jobs)) = args.try_get_one::<u64>("jobs") {
+                options.jobs = *jobs as usize;
+            }
+            exec::execute(config, values(args, "command"), options)
+        }
+        "fetch" => fetch::execute(config, flag(args, "prune")),
+        "git" => git::execute(config, values(args, "args")),
+        "grep" => {
+            let pattern = values(args, "pattern")
+                .into_iter()
+                .next()
+                .ok_or_else(|| {
+                    GitGlobalError::BadSubcommand(
+                        "grep requires a pattern argument".to_string(),
+                    )
+                })?;
+            let source = if flag(args, "cached") {
+                grep::Source::Index
+            } else if flag(args, "worktree") {
+                grep::Source::WorkTree
+            } else {
+                grep::Source::Head
+            };
+            let options = grep::GrepOptions {
+                ignore_case: flag(args, "ignore_case"),
+                source,
+                pathspecs: values(args, "pathspecs"),
+            };
+            grep::execute(config, &pattern, options)
+        }
+        "pull" => pull::execute(config, flag(args, "dry_run")),
+        "push" => push::execute(config, flag(args, "dry_run")),
         "install-manpage" => install_manpage::execute(config),
         "ignore" => {
-            let pattern = args.into_iter().next().ok_or_else(|| {
-                GitGlobalError::BadSubcommand(
-                    "ignore requires a pattern argument".to_string(),
-                )
-            })?;
+            let pattern = values(args, "pattern")
+                .into_iter()
+                .next()
+                .ok_or_else(|| {
+                    GitGlobalError::BadSubcommand(
+                        "ignore requires a pattern argument".to_string(),
+                    )
+                })?;
             ignore::execute(config, &pattern)
         }
         "ignored" => ignored::execute(config),
+        "add" => add::execute(config, paths(args)),
+        "forget" => forget::execute(config, paths(args)),
+        "tag" => {
+            let name = values(args, "name").into_iter().next();
+            tag::execute(config, name, values(args, "targets"))
+        }
+        "untag" => {
+            let name =
+                values(args, "name").into_iter().next().ok_or_else(|| {
+                    GitGlobalError::BadSubcommand(
+                        "untag requires a tag argument".to_string(),
+                    )
+                })?;
+            untag::execute(config, &name, values(args, "targets"))
+        }
+        "prune" => prune::execute(config, flag(args, "dry_run")),
+        "watch" => watch::execute(config),
         cmd => Err(GitGlobalError::BadSubcommand(cmd.to_string())),
     }
 }
 
+/// Returns the values given for a subcommand's argument, if any.
+fn values(args: &ArgMatches, id: &str) -> Vec<String> {
+    args.try_get_many::<String>(id)
+        .ok()
+        .flatten()
+        .map(|v| v.cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Returns the paths given for a subcommand's `paths` argument, if any.
+fn paths(args: &ArgMatches) -> Vec<PathBuf> {
+    values(args, "paths")
+        .into_iter()
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// Returns `true` if a subcommand's flag was given.
+fn flag(args: &ArgMatches, id: &str) -> bool {
+    args.try_get_one::<bool>(id)
+        .ok()
+        .flatten()
+        .copied()
+        .unwrap_or(false)
+}
+
 /// Return the list of all subcommand names and descriptions.
 ///
 /// Used for building the clap::Command in the cli module.
 pub fn get_subcommands() -> Vec<(&'static str, &'static str)> {
     vec![
+        (
+            "add",
+            "Adds repos to the list, whether or not a scan finds them",
+        ),
         (
             "ahead",
-            "Shows repos with changes that are not pushed to a remote",
+            "Shows branches with commits that are not on their upstreams",
+        ),
+        (
+            "behind",
+            "Shows branches whose upstreams have commits they don't",
+        ),
+        ("exec", "Runs a command in every repo, in parallel"),
+        ("fetch", "Fetches every remote of every repo, in parallel"),
+        (
+            "forget",
+            "Removes repos from the list, even if a scan finds them",
+        ),
+        ("git", "Runs git with the given arguments in every repo"),
+        (
+            "grep",
+            "Searches the tracked files of every repo for a pattern",
         ),
         ("ignore", "Ignores a repo, removing it from the list"),
         ("ignored", "Lists all ignored repos"),
@@ -72,6 +196,15 @@ pub fn get_subcommands() -> Vec<(&'static str, &'static str)> {
             "Attempts to install git-global's man page",
         ),
         ("list", "Lists all known repos"),
+        (
+            "prune",
+            "Removes repos that no longer exist or are not valid from the list",
+        ),
+        (
+            "pull",
+            "Fast-forwards clean repos that are behind their upstreams",
+        ),
+        ("push", "Pushes branches that are ahead of their upstreams"),
         ("scan", "Updates cache of known repos"),
         (
             "staged",
@@ -82,9 +215,18 @@ pub fn get_subcommands() -> Vec<(&'static str, &'static str)> {
             "status",
             "Shows status (`git status -s`) for repos with any changes",
         ),
+        (
+            "tag",
+            "Tags repos, for use with --tag, or lists tags and their repos",
+        ),
+        ("untag", "Removes a tag from repos"),
         (
             "unstaged",
             "Shows working dir status for repos with unstaged changes",
         ),
+        (
+            "watch",
+            "Keeps the cache of known repos current as repos come and go",
+        ),
     ]
 }
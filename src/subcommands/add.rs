@@ -0,0 +1,29 @@
+//! The `add` subcommand: registers repos by hand.
+//!
+//! Repos that were added by hand are known whether or not a scan finds them,
+//! so they can live outside of the configured base directories.
+
+use std::path::PathBuf;
+
+use crate::config::Config;
+use crate::errors::Result;
+use crate::report::Report;
+
+/// Adds each of the given paths as a known repo, if it is one.
+pub fn execute(config: Config, paths: Vec<PathBuf>) -> Result<Report> {
+    let mut report = Report::new(&[]);
+    for path in paths {
+        match config.add_repo(&path) {
+            Ok(repo) => report.add_message(format!("Added {}.", repo)),
+            Err(e) => {
+                report.add_message(format!(
+                    "Could not add {}: {}",
+                    path.display(),
+                    e
+                ));
+                report.mark_failed();
+            }
+        }
+    }
+    Ok(report)
+}
@@ -3,21 +3,38 @@
 use crate::config::Config;
 use crate::errors::Result;
 use crate::parallel::{default_parallelism, run_parallel};
-use crate::repo::Repo;
+use crate::repo::{Repo, RepoKind};
 use crate::report::Report;
 
 /// Runs the `ahead` subcommand.
+///
+/// Lists each local branch that has commits its upstream branch doesn't, with
+/// how far ahead of (and behind) the upstream it is.
 pub fn execute(mut config: Config) -> Result<Report> {
     let repos = config.get_repos();
     let mut report = Report::new(&repos);
+    report.pad_repo_output();
 
-    let results =
-        run_parallel(repos, default_parallelism(), |repo| repo.is_ahead());
+    let results = run_parallel(repos, default_parallelism(), |repo| {
+        // A linked worktree shares its branches with the main working tree,
+        // so they're only checked once, for the latter. Bare repos are
+        // usually remotes themselves, rather than places where work happens.
+        match repo.kind() {
+            RepoKind::Worktree | RepoKind::Bare => Ok(vec![]),
+            RepoKind::Normal | RepoKind::GitFile => repo.get_tracking_list(),
+        }
+    });
 
-    for (path, ahead) in results {
-        if ahead {
-            let repo = Repo::new(path);
-            report.add_repo_message(&repo, String::new());
+    for (path, tracking) in results {
+        let repo = Repo::new(path);
+        match tracking {
+            Ok(tracking) => {
+                for branch in tracking.iter().filter(|branch| branch.ahead > 0)
+                {
+                    report.add_repo_message(&repo, branch.to_string());
+                }
+            }
+            Err(e) => report.add_repo_error(&repo, &e),
         }
     }
 
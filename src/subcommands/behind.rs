@@ -0,0 +1,43 @@
+//! The `behind` subcommand: shows repositories with branches that are behind
+//! their upstream branches.
+
+use crate::config::Config;
+use crate::errors::Result;
+use crate::parallel::{default_parallelism, run_parallel};
+use crate::repo::{Repo, RepoKind};
+use crate::report::Report;
+
+/// Runs the `behind` subcommand.
+///
+/// Lists each local branch whose upstream branch has commits it doesn't, with
+/// how far behind (and ahead of) the upstream it is. Upstream branches are as
+/// of the last fetch; nothing is fetched here.
+pub fn execute(mut config: Config) -> Result<Report> {
+    let repos = config.get_repos();
+    let mut report = Report::new(&repos);
+    report.pad_repo_output();
+
+    let results = run_parallel(repos, default_parallelism(), |repo| {
+        // As with `ahead`, linked worktrees' branches are checked with their
+        // main working trees', and bare repos are left out.
+        match repo.kind() {
+            RepoKind::Worktree | RepoKind::Bare => Ok(vec![]),
+            RepoKind::Normal | RepoKind::GitFile => repo.get_tracking_list(),
+        }
+    });
+
+    for (path, tracking) in results {
+        let repo = Repo::new(path);
+        match tracking {
+            Ok(tracking) => {
+                for branch in tracking.iter().filter(|branch| branch.behind > 0)
+                {
+                    report.add_repo_message(&repo, branch.to_string());
+                }
+            }
+            Err(e) => report.add_repo_error(&repo, &e),
+        }
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,201 @@
+//! The `exec` subcommand: runs a command in every known repo.
+//!
+//! The command runs with each repo as its working directory, several repos at
+//! a time, and its output and exit status are reported per repo, in the same
+//! order as every other subcommand's:
+//!
+//! ```bash
+//! $ git global exec -- git log -1 --format=%s
+//! /home/me/src/project
+//! Fix the frobnicator
+//!
+//! /home/me/src/other
+//! Add a README
+//! ```
+//!
+//! The command gets the repo's path in the `GIT_GLOBAL_REPO` environment
+//! variable, and the repo's directory name in `GIT_GLOBAL_REPO_NAME`, so that
+//! commands run through a shell can use them. Its standard input is empty.
+//!
+//! With `--jobs <N>`, at most `N` commands run at once. With `--fail-fast`,
+//! no more commands are started once one fails. With `--only-failed`, output
+//! is only shown for the repos where the command failed. If the command
+//! fails anywhere, `git global` exits with a nonzero status.
+
+use std::process::{Command, ExitStatus, Output, Stdio};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde_json::json;
+
+use crate::config::Config;
+use crate::errors::{GitGlobalError, Result};
+use crate::parallel::{default_parallelism, run_parallel};
+use crate::repo::Repo;
+use crate::report::Report;
+
+/// Options for how a command is run across repos.
+#[derive(Clone, Debug)]
+pub struct ExecOptions {
+    /// The most commands to run at once.
+    pub jobs: usize,
+    /// Whether to stop starting commands once one fails.
+    pub fail_fast: bool,
+    /// Whether to only report the repos where the command failed.
+    pub only_failed: bool,
+}
+
+impl Default for ExecOptions {
+    fn default() -> Self {
+        ExecOptions {
+            jobs: default_parallelism(),
+            fail_fast: false,
+            only_failed: false,
+        }
+    }
+}
+
+/// The outcome of running the command in one repo.
+enum Outcome {
+    Finished(Output),
+    /// The command couldn't be started, e.g., because it wasn't found.
+    NotStarted(String),
+    /// The command wasn't started, because it failed elsewhere first.
+    Skipped,
+}
+
+/// Runs `command` (a program and its arguments) in every repo.
+pub fn execute(
+    mut config: Config,
+    command: Vec<String>,
+    options: ExecOptions,
+) -> Result<Report> {
+    let Some((program, args)) = command.split_first() else {
+        return Err(GitGlobalError::BadSubcommand(
+            "exec requires a command to run".to_string(),
+        ));
+    };
+    let (program, args) = (program.clone(), args.to_vec());
+    let repos = config.get_repos();
+    let mut report = Report::new(&repos);
+    report.pad_repo_output();
+
+    let n_repos = repos.len();
+    let failed = Arc::new(AtomicBool::new(false));
+    let fail_fast = options.fail_fast;
+    let results = run_parallel(repos, options.jobs.max(1), move |repo| {
+        if fail_fast && failed.load(Ordering::SeqCst) {
+            return Outcome::Skipped;
+        }
+        let outcome = run(repo, &program, &args);
+        if !succeeded(&outcome) {
+            failed.store(true, Ordering::SeqCst);
+        }
+        outcome
+    });
+
+    let mut n_failed = 0;
+    let mut skipped = Vec::new();
+    let mut data = Vec::new();
+    for (path, outcome) in results {
+        let repo = Repo::new(&path);
+        let mut lines = Vec::new();
+        match &outcome {
+            Outcome::Finished(output) => {
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                lines.extend(stdout.lines().map(String::from));
+                lines.extend(stderr.lines().map(String::from));
+                if !output.status.success() {
+                    lines.push(describe(output.status));
+                }
+                data.push(json!({
+                    "path": path,
+                    "status": output.status.code(),
+                    "success": output.status.success(),
+                    "stdout": stdout,
+                    "stderr": stderr,
+                }));
+            }
+            Outcome::NotStarted(e) => {
+                lines.push(format!("Could not run the command: {}", e));
+                data.push(json!({
+                    "path": path,
+                    "status": null,
+                    "success": false,
+                    "error": e,
+                }));
+            }
+            Outcome::Skipped => {
+                skipped.push(path);
+                continue;
+            }
+        }
+        let success = succeeded(&outcome);
+        if !success {
+            n_failed += 1;
+        }
+        if success && options.only_failed {
+            continue;
+        }
+        for line in lines {
+            report.add_repo_message(&repo, line);
+        }
+    }
+
+    if n_failed > 0 {
+        report.mark_failed();
+        report.add_message(format!(
+            "The command failed in {} of {} repos.",
+            n_failed, n_repos
+        ));
+    }
+    if !skipped.is_empty() {
+        report.add_message(format!(
+            "Skipped {} {} after the command failed.",
+            skipped.len(),
+            if skipped.len() == 1 { "repo" } else { "repos" }
+        ));
+    }
+    report.add_data("results", json!(data));
+    report.add_data("skipped", json!(skipped));
+
+    Ok(report)
+}
+
+/// Runs a program in a repo, capturing its output.
+fn run(repo: &Repo, program: &str, args: &[String]) -> Outcome {
+    let path = repo.path();
+    let name = std::path::Path::new(&path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let output = Command::new(program)
+        .args(args)
+        .current_dir(&path)
+        .env("GIT_GLOBAL_REPO", &path)
+        .env("GIT_GLOBAL_REPO_NAME", name)
+        .stdin(Stdio::null())
+        .output();
+    match output {
+        Ok(output) => Outcome::Finished(output),
+        Err(e) => Outcome::NotStarted(e.to_string()),
+    }
+}
+
+/// Returns `false` if the command failed, or couldn't be started.
+fn succeeded(outcome: &Outcome) -> bool {
+    match outcome {
+        Outcome::Finished(output) => output.status.success(),
+        Outcome::NotStarted(_) => false,
+        Outcome::Skipped => true,
+    }
+}
+
+/// Describes how a command that failed exited.
+fn describe(status: ExitStatus) -> String {
+    match status.code() {
+        Some(code) => format!("Exited with status {}.", code),
+        None => format!("Terminated ({}).", status),
+    }
+}
@@ -0,0 +1,103 @@
+//! The `fetch` subcommand: fetches every remote of every known repo.
+//!
+//! Repos are fetched in parallel, and each remote's outcome is reported,
+//! whether it was fetched or why it couldn't be (e.g., an unreachable host or
+//! missing credentials):
+//!
+//! ```bash
+//! $ git global fetch
+//! /home/me/src/project
+//! origin: 3 refs updated
+//!
+//! /home/me/src/offline
+//! origin: failed: failed to resolve address for example.com
+//! ```
+//!
+//! If any remote can't be fetched, `git global` exits with a nonzero status.
+//!
+//! With `--prune`, remote-tracking refs for branches that are gone from their
+//! remotes are removed, as with `git fetch --prune`.
+
+use serde_json::json;
+
+use crate::config::Config;
+use crate::errors::Result;
+use crate::parallel::{default_parallelism, run_parallel};
+use crate::repo::{Repo, RepoKind};
+use crate::report::Report;
+
+/// Runs the `fetch` subcommand.
+pub fn execute(mut config: Config, prune: bool) -> Result<Report> {
+    let repos = config.get_repos();
+    let mut report = Report::new(&repos);
+    report.pad_repo_output();
+
+    let results = run_parallel(repos, default_parallelism(), move |repo| {
+        // A linked worktree shares its remotes with the main working tree,
+        // so they're only fetched once, for the latter.
+        if repo.kind() == RepoKind::Worktree {
+            return Ok(vec![]);
+        }
+        repo.fetch(prune)
+    });
+
+    let mut n_fetched = 0;
+    let mut failures = Vec::new();
+    let mut n_unreadable = 0;
+    for (path, fetches) in results {
+        let repo = Repo::new(&path);
+        let fetches = match fetches {
+            Ok(fetches) => fetches,
+            Err(e) => {
+                failures.push(json!({
+                    "path": path,
+                    "remote": null,
+                    "error": e.message(),
+                }));
+                report.add_repo_message(
+                    &repo,
+                    format!("Could not read remotes: {}", e.message()),
+                );
+                n_unreadable += 1;
+                continue;
+            }
+        };
+        for (remote, result) in fetches {
+            let line = match result {
+                Ok(0) => format!("{}: up to date", remote),
+                Ok(1) => format!("{}: 1 ref updated", remote),
+                Ok(n) => format!("{}: {} refs updated", remote, n),
+                Err(e) => {
+                    failures.push(json!({
+                        "path": path,
+                        "remote": remote,
+                        "error": e.message(),
+                    }));
+                    format!("{}: failed: {}", remote, e.message())
+                }
+            };
+            n_fetched += 1;
+            report.add_repo_message(&repo, line);
+        }
+    }
+    let n_failed = failures.len() - n_unreadable;
+    if n_failed > 0 {
+        report.add_message(format!(
+            "Could not fetch {} of {} remotes.",
+            n_failed, n_fetched
+        ));
+    }
+    if n_unreadable > 0 {
+        report.add_message(format!(
+            "Could not read the remotes of {} {}.",
+            n_unreadable,
+            if n_unreadable == 1 { "repo" } else { "repos" }
+        ));
+    }
+    if !failures.is_empty() {
+        report.mark_failed();
+    }
+    report.add_data("failed", json!(failures));
+
+    Ok(report)
+}
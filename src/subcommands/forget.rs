@@ -0,0 +1,32 @@
+//! The `forget` subcommand: drops repos from the list of known repos.
+//!
+//! Forgotten repos stay forgotten, even when a scan finds them again, until
+//! they're registered again with `git global add`.
+
+use std::path::PathBuf;
+
+use crate::config::Config;
+use crate::errors::Result;
+use crate::report::Report;
+
+/// Forgets each of the given repos.
+pub fn execute(config: Config, paths: Vec<PathBuf>) -> Result<Report> {
+    let mut report = Report::new(&[]);
+    for path in paths {
+        match config.forget_repo(&path) {
+            Ok(repo) => report.add_message(format!(
+                "Forgot {}. Use `git global add` to list it again.",
+                repo
+            )),
+            Err(e) => {
+                report.add_message(format!(
+                    "Could not forget {}: {}",
+                    path.display(),
+                    e
+                ));
+                report.mark_failed();
+            }
+        }
+    }
+    Ok(report)
+}
@@ -0,0 +1,24 @@
+//! The `git` subcommand: runs git with the given arguments in every repo.
+//!
+//! It's shorthand for `git global exec -- git <ARGS...>`, with the output
+//! grouped by repo:
+//!
+//! ```bash
+//! $ git global git log -1 --oneline
+//! /home/me/src/project
+//! 1a2b3c4 Fix the frobnicator
+//!
+//! /home/me/src/other
+//! 5d6e7f8 Add a README
+//! ```
+
+use crate::config::Config;
+use crate::errors::Result;
+use crate::report::Report;
+use crate::subcommands::exec::{self, ExecOptions};
+
+/// Runs git with `args` in every repo.
+pub fn execute(config: Config, args: Vec<String>) -> Result<Report> {
+    let command = std::iter::once("git".to_string()).chain(args).collect();
+    exec::execute(config, command, ExecOptions::default())
+}
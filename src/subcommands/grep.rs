@@ -0,0 +1,221 @@
+//! The `grep` subcommand: searches the tracked files of every repo.
+//!
+//! Each repo's files are read straight from git, in parallel, and every line
+//! that matches a regular expression is printed as `repo:path:line:match`,
+//! grouped by repo:
+//!
+//! ```bash
+//! $ git global grep -i 'todo' -- '*.rs'
+//! /home/me/src/project:src/main.rs:12:    // TODO: handle errors
+//! /home/me/src/other:lib.rs:40:// Todo: remove this
+//! ```
+//!
+//! By default, the files as of the current commit (HEAD) are searched. With
+//! `--cached`, the files in the index are searched instead, and with
+//! `--worktree`, the working tree's copies of the files in the index are.
+//! Pathspecs, like `git grep`'s, are relative to the top of each repo.
+//! Binary files are skipped. If nothing matches, or a repo can't be
+//! searched, `git global` exits with a nonzero status.
+
+use std::fs;
+use std::path::Path;
+
+use regex::{Regex, RegexBuilder};
+use serde_json::json;
+
+use crate::config::Config;
+use crate::errors::{GitGlobalError, Result};
+use crate::parallel::{default_parallelism, run_parallel};
+use crate::repo::Repo;
+use crate::report::Report;
+
+/// Where to read the tracked files' contents from.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Source {
+    /// The tree of the current commit.
+    #[default]
+    Head,
+    /// The index.
+    Index,
+    /// The working tree, for the files in the index.
+    WorkTree,
+}
+
+/// Options for what `grep` searches, and how.
+#[derive(Clone, Debug, Default)]
+pub struct GrepOptions {
+    pub ignore_case: bool,
+    pub source: Source,
+    /// Only files matching one of these pathspecs are searched, unless
+    /// there are none.
+    pub pathspecs: Vec<String>,
+}
+
+/// A line that matched.
+struct Match {
+    path: String,
+    line: usize,
+    text: String,
+}
+
+/// Searches every repo for lines that match `pattern`, a regular expression.
+pub fn execute(
+    mut config: Config,
+    pattern: &str,
+    options: GrepOptions,
+) -> Result<Report> {
+    let regex = match RegexBuilder::new(pattern)
+        .case_insensitive(options.ignore_case)
+        .build()
+    {
+        Ok(regex) => regex,
+        Err(e) => {
+            return Err(GitGlobalError::Failed(format!(
+                "Invalid pattern: {}",
+                e
+            )));
+        }
+    };
+    let repos = config.get_repos();
+    let mut report = Report::new(&repos);
+    report.omit_repo_headers();
+
+    let source = options.source;
+    let pathspecs = options.pathspecs;
+    let results = run_parallel(repos, default_parallelism(), move |repo| {
+        search(repo, &regex, source, &pathspecs)
+    });
+
+    let mut data = Vec::new();
+    for (path, result) in results {
+        match result {
+            Ok(matches) => {
+                let repo = Repo::new(&path);
+                for m in matches {
+                    report.add_repo_message(
+                        &repo,
+                        format!("{}:{}:{}:{}", path, m.path, m.line, m.text),
+                    );
+                    data.push(json!({
+                        "repo": path,
+                        "path": m.path,
+                        "line": m.line,
+                        "text": m.text,
+                    }));
+                }
+            }
+            Err(e) => {
+                report.add_message(format!(
+                    "Could not search {}: {}",
+                    path,
+                    e.message()
+                ));
+                report.mark_failed();
+            }
+        }
+    }
+    if data.is_empty() {
+        // Like `git grep`, exit with a nonzero status if nothing matched.
+        report.mark_failed();
+    }
+    report.add_data("matches", json!(data));
+
+    Ok(report)
+}
+
+/// Searches the tracked files of a repo.
+fn search(
+    repo: &Repo,
+    regex: &Regex,
+    source: Source,
+    pathspecs: &[String],
+) -> std::result::Result<Vec<Match>, git2::Error> {
+    let git2_repo = git2::Repository::open(repo.path())?;
+    let pathspec = git2::Pathspec::new(pathspecs.iter())?;
+    let selected = |path: &str| {
+        pathspecs.is_empty()
+            || pathspec
+                .matches_path(Path::new(path), git2::PathspecFlags::DEFAULT)
+    };
+    let mut matches = Vec::new();
+    match source {
+        Source::Head => {
+            let tree = match git2_repo.head() {
+                Ok(head) => head.peel_to_tree()?,
+                // There are no commits, so there are no files.
+                Err(e) if e.code() == git2::ErrorCode::UnbornBranch => {
+                    return Ok(matches);
+                }
+                Err(e) => return Err(e),
+            };
+            let mut files = Vec::new();
+            tree.walk(git2::TreeWalkMode::PreOrder, |dir, entry| {
+                if entry.kind() == Some(git2::ObjectType::Blob)
+                    && let Ok(name) = entry.name()
+                {
+                    files.push((format!("{}{}", dir, name), entry.id()));
+                }
+                git2::TreeWalkResult::Ok
+            })?;
+            for (path, id) in files.into_iter().filter(|(p, _)| selected(p)) {
+                let blob = git2_repo.find_blob(id)?;
+                search_file(&path, blob.content(), regex, &mut matches);
+            }
+        }
+        Source::Index | Source::WorkTree => {
+            if git2_repo.is_bare() {
+                return Ok(matches);
+            }
+            let workdir = git2_repo.workdir().map(Path::to_path_buf);
+            let index = git2_repo.index()?;
+            for entry in index.iter() {
+                // Submodules are repos of their own.
+                if entry.mode == 0o160000 {
+                    continue;
+                }
+                let path = String::from_utf8_lossy(&entry.path).into_owned();
+                if !selected(&path) {
+                    continue;
+                }
+                if source == Source::Index {
+                    let blob = git2_repo.find_blob(entry.id)?;
+                    search_file(&path, blob.content(), regex, &mut matches);
+                } else if let Some(workdir) = &workdir
+                    // Files that were deleted have nothing to search.
+                    && let Ok(content) = fs::read(workdir.join(&path))
+                {
+                    search_file(&path, &content, regex, &mut matches);
+                }
+            }
+        }
+    }
+    Ok(matches)
+}
+
+/// Adds the lines of a file that match to `matches`, unless it's binary.
+fn search_file(
+    path: &str,
+    content: &[u8],
+    regex: &Regex,
+    matches: &mut Vec<Match>,
+) {
+    // Like git, consider files with a NUL byte near the start binary.
+    if content.iter().take(8000).any(|&b| b == 0) {
+        return;
+    }
+    if content.is_empty() {
+        return;
+    }
+    let content = content.strip_suffix(b"\n").unwrap_or(content);
+    for (i, line) in content.split(|&b| b == b'\n').enumerate() {
+        let line = String::from_utf8_lossy(line);
+        let line = line.strip_suffix('\r').unwrap_or(&line);
+        if regex.is_match(line) {
+            matches.push(Match {
+                path: path.to_string(),
+                line: i + 1,
+                text: line.to_string(),
+            });
+        }
+    }
+}
@@ -2,16 +2,18 @@
 
 use crate::config::Config;
 use crate::errors::{GitGlobalError, Result};
+use crate::pattern::IgnorePattern;
 use crate::report::Report;
 
 /// Adds the given pattern to global.ignore in gitconfig.
 pub fn execute(config: Config, pattern: &str) -> Result<Report> {
     let mut report = Report::new(&[]);
     match config.add_ignore_pattern(pattern) {
-        Ok(()) => {
+        Ok(added) => {
+            let style = IgnorePattern::new(&added, config.ignore_style).style();
             report.add_message(format!(
-                "Added '{}' to global.ignore. Run `git global scan` to update the cache.",
-                pattern
+                "Added '{}' to global.ignore as a {} pattern. Run `git global scan` to update the cache.",
+                added, style
             ));
         }
         Err(e) => {
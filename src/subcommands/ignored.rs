@@ -4,13 +4,10 @@ use crate::config::Config;
 use crate::errors::Result;
 use crate::report::Report;
 
-/// Lists all patterns currently in global.ignore.
+/// Lists all patterns currently in global.ignore, along with how each one is
+/// matched.
 pub fn execute(config: Config) -> Result<Report> {
-    let patterns: Vec<&String> = config
-        .ignored_patterns
-        .iter()
-        .filter(|p| !p.is_empty())
-        .collect();
+    let patterns = &config.ignored_patterns;
 
     let mut report = Report::new(&[]);
     if patterns.is_empty() {
@@ -21,7 +18,17 @@ pub fn execute(config: Config) -> Result<Report> {
     } else {
         report.add_message(format!("Ignored patterns ({}):", patterns.len()));
         for pattern in patterns {
-            report.add_message(format!("  {}", pattern));
+            let negated = if pattern.is_negated() {
+                ", negated"
+            } else {
+                ""
+            };
+            report.add_message(format!(
+                "  {} ({}{})",
+                pattern,
+                pattern.style(),
+                negated
+            ));
         }
     }
     Ok(report)
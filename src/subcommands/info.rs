@@ -4,7 +4,7 @@ use clap::crate_version;
 
 use std::env;
 use std::path::PathBuf;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 use crate::config::Config;
 use crate::errors::Result;
@@ -17,19 +17,23 @@ fn get_age(filename: PathBuf) -> Option<String> {
         .ok()
         .and_then(|metadata| metadata.modified().ok())
         .and_then(|mtime| SystemTime::now().duration_since(mtime).ok())
-        .map(|dur| {
-            let ts = dur.as_secs();
-            let days = ts / (24 * 60 * 60);
-            let hours = (ts / (60 * 60)) - (days * 24);
-            let mins = (ts / 60) - (days * 24 * 60) - (hours * 60);
-            let secs =
-                ts - (days * 24 * 60 * 60) - (hours * 60 * 60) - (mins * 60);
-            format!("{}d, {}h, {}m, {}s", days, hours, mins, secs)
-        })
+        .map(format_duration)
+}
+
+/// Formats a duration in terms of days, hours, minutes, and seconds.
+fn format_duration(dur: Duration) -> String {
+    let ts = dur.as_secs();
+    let days = ts / (24 * 60 * 60);
+    let hours = (ts / (60 * 60)) - (days * 24);
+    let mins = (ts / 60) - (days * 24 * 60) - (hours * 60);
+    let secs = ts - (days * 24 * 60 * 60) - (hours * 60 * 60) - (mins * 60);
+    format!("{}d, {}h, {}m, {}s", days, hours, mins, secs)
 }
 
 /// Gathers metadata about the git-global installation.
 pub fn execute(mut config: Config) -> Result<Report> {
+    // Getting the repos refreshes a stale cache, so check it first.
+    let stale = config.cache_is_stale();
     let repos = config.get_repos();
     let mut report = Report::new(&repos);
     let version = crate_version!().to_string();
@@ -41,7 +45,10 @@ pub fn execute(mut config: Config) -> Result<Report> {
     report.add_message(format!("git-global {}", version));
     report.add_message(underline);
     report.add_message(format!("Number of repos: {}", repos.len()));
-    report.add_message(format!("Base directory: {}", config.basedir.display()));
+    report.add_message("Base directories:".to_string());
+    for basedir in config.basedirs.iter() {
+        report.add_message(format!("  {}", basedir));
+    }
     report.add_message("Ignored patterns:".to_string());
     for pat in config.ignored_patterns.iter() {
         report.add_message(format!("  {}", pat));
@@ -57,6 +64,19 @@ pub fn execute(mut config: Config) -> Result<Report> {
     } else {
         report.add_message("Cache file: <none>".to_string());
     }
+    match config.cache_ttl {
+        Some(ttl) => {
+            report.add_message(format!("Cache TTL: {}", format_duration(ttl)))
+        }
+        None => report.add_message("Cache TTL: <none>".to_string()),
+    }
+    if stale {
+        report.add_message(
+            "Cache stale: yes (it was rescanned just now)".to_string(),
+        );
+    } else {
+        report.add_message("Cache stale: no".to_string());
+    }
     if let Some(manpage_file) = config.manpage_file {
         report.add_message(format!("Manpage file: {}", manpage_file.display()));
     } else {
@@ -0,0 +1,53 @@
+//! The `prune` subcommand: removes stale repos from the cache.
+//!
+//! Repos that were deleted, or that are no longer valid git repos, stay in
+//! the cache until the next scan (or forever, if they were added by hand).
+//! `prune` checks every known repo and removes those:
+//!
+//! ```bash
+//! $ git global prune --dry-run  # only show what would be removed
+//! $ git global prune
+//! ```
+
+use serde_json::json;
+
+use crate::config::Config;
+use crate::errors::Result;
+use crate::report::Report;
+
+/// Prunes the cache, listing each repo that was (or, with `dry_run`, would
+/// be) removed, and why.
+pub fn execute(config: Config, dry_run: bool) -> Result<Report> {
+    let mut report = Report::new(&[]);
+    let pruned = match config.prune_cache(dry_run) {
+        Ok(pruned) => pruned,
+        Err(e) => {
+            report.add_message(format!("Could not prune: {}", e));
+            report.mark_failed();
+            return Ok(report);
+        }
+    };
+    report.add_data(
+        "pruned",
+        json!(
+            pruned
+                .iter()
+                .map(|(repo, reason)| json!({
+                    "path": repo.path(),
+                    "reason": reason,
+                }))
+                .collect::<Vec<_>>()
+        ),
+    );
+    if pruned.is_empty() {
+        report.add_message("Nothing to prune.".to_string());
+        return Ok(report);
+    }
+    let verb = if dry_run { "Would prune" } else { "Pruned" };
+    let noun = if pruned.len() == 1 { "repo" } else { "repos" };
+    report.add_message(format!("{} {} {}:", verb, pruned.len(), noun));
+    for (repo, reason) in &pruned {
+        report.add_message(format!("  {} ({})", repo, reason));
+    }
+    Ok(report)
+}
@@ -0,0 +1,215 @@
+//! The `pull` subcommand: fast-forwards repos that are behind their
+//! upstreams.
+//!
+//! For each repo whose current branch is strictly behind its upstream branch,
+//! as of the last fetch, `pull` moves the branch (and the working tree) up to
+//! the upstream. It never merges or rebases, so repos that can't simply be
+//! fast-forwarded are skipped, with the reason:
+//!
+//! ```bash
+//! $ git global fetch
+//! $ git global pull --dry-run  # only show what would be fast-forwarded
+//! $ git global pull
+//! /home/me/src/project
+//! Fast-forwarded main by 2 commits to origin/main (1a2b3c4..5d6e7f8).
+//!
+//! /home/me/src/experiment
+//! Skipped: main has diverged from origin/main [ahead 1, behind 3].
+//! ```
+//!
+//! Repos are skipped if they have uncommitted changes to tracked files, if
+//! HEAD is detached, if the branch has diverged from its upstream, or if an
+//! operation such as a rebase or merge is in progress. If any repo can't be
+//! fast-forwarded for another reason, `git global` exits with a nonzero
+//! status.
+
+use serde_json::json;
+
+use crate::config::Config;
+use crate::errors::Result;
+use crate::parallel::{default_parallelism, run_parallel};
+use crate::repo::{self, Fact, FactValue, Repo, Tracking};
+use crate::report::Report;
+
+/// What pulling did, or would do, to a repo.
+enum Outcome {
+    /// The current branch isn't behind an upstream branch.
+    Nothing,
+    FastForward {
+        tracking: Tracking,
+        from: git2::Oid,
+        to: git2::Oid,
+    },
+    Skipped(String),
+    Failed(String),
+}
+
+/// Runs the `pull` subcommand, only reporting what would be fast-forwarded if
+/// `dry_run` is set.
+pub fn execute(mut config: Config, dry_run: bool) -> Result<Report> {
+    let repos = config.get_repos();
+    let mut report = Report::new(&repos);
+    report.pad_repo_output();
+
+    let results = run_parallel(repos, default_parallelism(), move |repo| {
+        pull(repo, dry_run).unwrap_or_else(|e| Outcome::Failed(e.to_string()))
+    });
+
+    let mut pulled = Vec::new();
+    let mut skipped = Vec::new();
+    let mut failed = Vec::new();
+    for (path, outcome) in results {
+        let repo = Repo::new(&path);
+        let line = match outcome {
+            Outcome::Nothing => continue,
+            Outcome::FastForward {
+                tracking,
+                from,
+                to,
+            } => {
+                pulled.push(json!({
+                    "path": path,
+                    "branch": tracking.branch,
+                    "upstream": tracking.upstream,
+                    "from": from.to_string(),
+                    "to": to.to_string(),
+                    "commits": tracking.behind,
+                }));
+                format!(
+                    "{} {} by {} {} to {} ({:.7}..{:.7}).",
+                    if dry_run {
+                        "Would fast-forward"
+                    } else {
+                        "Fast-forwarded"
+                    },
+                    tracking.branch,
+                    tracking.behind,
+                    if tracking.behind == 1 {
+                        "commit"
+                    } else {
+                        "commits"
+                    },
+                    tracking.upstream,
+                    from.to_string(),
+                    to.to_string()
+                )
+            }
+            Outcome::Skipped(reason) => {
+                let line = format!("Skipped: {}.", reason);
+                skipped.push(json!({"path": path, "reason": reason}));
+                line
+            }
+            Outcome::Failed(e) => {
+                let line = format!("Could not fast-forward: {}", e);
+                failed.push(json!({"path": path, "error": e}));
+                line
+            }
+        };
+        report.add_repo_message(&repo, line);
+    }
+    if !failed.is_empty() {
+        report.mark_failed();
+    }
+    report.add_data("pulled", json!(pulled));
+    report.add_data("skipped", json!(skipped));
+    report.add_data("failed", json!(failed));
+
+    Ok(report)
+}
+
+/// Fast-forwards the current branch of a repo to its upstream branch, if
+/// that's all it takes to bring it up to date.
+fn pull(
+    repo: &Repo,
+    dry_run: bool,
+) -> std::result::Result<Outcome, git2::Error> {
+    let mut git2_repo = git2::Repository::open(repo.path())?;
+    if git2_repo.is_bare() {
+        return Ok(Outcome::Nothing);
+    }
+    if let Some(operation) = operation_in_progress(git2_repo.state()) {
+        return Ok(Outcome::Skipped(format!("a {} is in progress", operation)));
+    }
+    let (tracking, from, to) = {
+        let head = match git2_repo.head() {
+            Ok(head) => head,
+            // There are no commits yet.
+            Err(e) if e.code() == git2::ErrorCode::UnbornBranch => {
+                return Ok(Outcome::Nothing);
+            }
+            Err(e) => return Err(e),
+        };
+        if !head.is_branch() {
+            return Ok(Outcome::Skipped("HEAD is detached".to_string()));
+        }
+        let branch = git2::Branch::wrap(head);
+        let Some(tracking) = repo::tracking(&git2_repo, &branch) else {
+            return Ok(Outcome::Nothing);
+        };
+        if tracking.behind == 0 {
+            return Ok(Outcome::Nothing);
+        }
+        if tracking.ahead > 0 {
+            return Ok(Outcome::Skipped(format!(
+                "{} has diverged from {} [ahead {}, behind {}]",
+                tracking.branch,
+                tracking.upstream,
+                tracking.ahead,
+                tracking.behind
+            )));
+        }
+        let from = branch.get().peel_to_commit()?.id();
+        let to = branch.upstream()?.get().peel_to_commit()?.id();
+        (tracking, from, to)
+    };
+    if repo.fact(&mut git2_repo, Fact::Dirty) == FactValue::Bool(true) {
+        return Ok(Outcome::Skipped(
+            "the working tree has uncommitted changes".to_string(),
+        ));
+    }
+    if !dry_run {
+        // Lock the branch before touching the working tree, so that nothing
+        // else can move it until the working tree has been moved, too.
+        let refname = format!("refs/heads/{}", tracking.branch);
+        let mut transaction = git2_repo.transaction()?;
+        transaction.lock_ref(&refname)?;
+        if git2_repo.refname_to_id(&refname)? != from {
+            return Err(git2::Error::from_str(&format!(
+                "{} moved while it was being pulled",
+                tracking.branch
+            )));
+        }
+        // A safe checkout refuses to overwrite anything, such as untracked
+        // files in the way, and changes nothing if it can't finish.
+        let target = git2_repo.find_commit(to)?;
+        let mut checkout = git2::build::CheckoutBuilder::new();
+        checkout.safe();
+        git2_repo.checkout_tree(target.as_object(), Some(&mut checkout))?;
+        transaction.set_target(
+            &refname,
+            to,
+            None,
+            &format!("pull: fast-forward to {}", tracking.upstream),
+        )?;
+        transaction.commit()?;
+    }
+    Ok(Outcome::FastForward {
+        tracking,
+        from,
+        to,
+    })
+}
+
+/// Returns the name of the operation that's in progress in a repo, if any.
+fn operation_in_progress(state: git2::RepositoryState) -> Option<&'static str> {
+    use git2::RepositoryState::*;
+    match state {
+        Clean => None,
+        Merge => Some("merge"),
+        Revert | RevertSequence => Some("revert"),
+        CherryPick | CherryPickSequence => Some("cherry-pick"),
+        Bisect => Some("bisect"),
+        Rebase | RebaseInteractive | RebaseMerge => Some("rebase"),
+        ApplyMailbox | ApplyMailboxOrRebase => Some("`git am`"),
+    }
+}
@@ -0,0 +1,117 @@
+//! The `push` subcommand: pushes branches that are ahead of their upstreams.
+//!
+//! For each repo that `git global ahead` would list, `push` pushes every
+//! branch that is strictly ahead of its upstream branch, as of the last
+//! fetch. Pushes are always fast-forwards: branches that have diverged from
+//! their upstreams are skipped, and a push that wouldn't fast-forward the
+//! remote's branch (because it moved since the last fetch) fails instead of
+//! overwriting it:
+//!
+//! ```bash
+//! $ git global push --dry-run  # only show what would be pushed
+//! $ git global push
+//! /home/me/src/project
+//! Pushed main to origin/main (2 commits).
+//! Skipped: topic has diverged from origin/topic [ahead 1, behind 3].
+//! ```
+//!
+//! If any push fails, `git global` exits with a nonzero status.
+
+use serde_json::json;
+
+use crate::config::Config;
+use crate::errors::Result;
+use crate::parallel::{default_parallelism, run_parallel};
+use crate::repo::{Repo, RepoKind, Tracking};
+use crate::report::Report;
+
+/// Runs the `push` subcommand, only reporting what would be pushed if
+/// `dry_run` is set.
+pub fn execute(mut config: Config, dry_run: bool) -> Result<Report> {
+    let repos = config.get_repos();
+    let mut report = Report::new(&repos);
+    report.pad_repo_output();
+
+    let results = run_parallel(repos, default_parallelism(), move |repo| {
+        // As with `ahead`, linked worktrees' branches are pushed with their
+        // main working trees', and bare repos are left out.
+        let tracking = match repo.kind() {
+            RepoKind::Worktree | RepoKind::Bare => vec![],
+            RepoKind::Normal | RepoKind::GitFile => repo.get_tracking_list()?,
+        };
+        Ok(tracking
+            .into_iter()
+            .filter(|branch| branch.ahead > 0)
+            .map(|branch| {
+                let result = if branch.behind > 0 || dry_run {
+                    Ok(())
+                } else {
+                    repo.push(&branch.branch)
+                        .map_err(|e| e.message().to_string())
+                };
+                (branch, result)
+            })
+            .collect::<Vec<(Tracking, std::result::Result<(), String>)>>())
+    });
+
+    let mut pushed = Vec::new();
+    let mut failed = Vec::new();
+    for (path, branches) in results {
+        let repo = Repo::new(&path);
+        let branches = match branches {
+            Ok(branches) => branches,
+            Err(e) => {
+                report.add_repo_error(&repo, &e);
+                continue;
+            }
+        };
+        for (branch, result) in branches {
+            let line = if branch.behind > 0 {
+                format!(
+                    "Skipped: {} has diverged from {} [ahead {}, behind {}].",
+                    branch.branch, branch.upstream, branch.ahead, branch.behind
+                )
+            } else if let Err(e) = result {
+                let line = format!(
+                    "Could not push {} to {}: {}",
+                    branch.branch, branch.upstream, e
+                );
+                failed.push(json!({
+                    "path": path,
+                    "branch": branch.branch,
+                    "upstream": branch.upstream,
+                    "error": e,
+                }));
+                line
+            } else {
+                let line = format!(
+                    "{} {} to {} ({} {}).",
+                    if dry_run { "Would push" } else { "Pushed" },
+                    branch.branch,
+                    branch.upstream,
+                    branch.ahead,
+                    if branch.ahead == 1 {
+                        "commit"
+                    } else {
+                        "commits"
+                    },
+                );
+                pushed.push(json!({
+                    "path": path,
+                    "branch": branch.branch,
+                    "upstream": branch.upstream,
+                    "commits": branch.ahead,
+                }));
+                line
+            };
+            report.add_repo_message(&repo, line);
+        }
+    }
+    if !failed.is_empty() {
+        report.mark_failed();
+    }
+    report.add_data("pushed", json!(pushed));
+    report.add_data("failed", json!(failed));
+
+    Ok(report)
+}
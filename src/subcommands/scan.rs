@@ -7,7 +7,8 @@
 //! $ git config --global global.basedir /some/path
 //! ```
 //!
-//! Additional directories can be scanned by passing them as arguments:
+//! More base directories can be added with `git config --global --add`.
+//! Additional directories can be scanned once by passing them as arguments:
 //!
 //! ```bash
 //! $ git global scan /extra/path1 /extra/path2
@@ -15,23 +16,92 @@
 //!
 //! The `scan` subcommand caches the list of git repos paths it finds, and can
 //! be rerun at any time to refresh the list.
+//!
+//! With `--incremental`, only directories that changed since the last scan are
+//! read again; the rest are listed from a snapshot saved by that scan:
+//!
+//! ```bash
+//! $ git global scan --incremental
+//! ```
 
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
-use crate::config::Config;
+use serde_json::json;
+
+use crate::config::{Config, ScanError, ScanMode};
 use crate::errors::Result;
+use crate::repo::Repo;
 use crate::report::Report;
 
-/// Clears the cache, forces a rescan, and says how many repos were found.
+/// Rescans, replacing the cache, and says how many repos were found and which
+/// were added or removed since the last scan.
 pub fn execute(
     mut config: Config,
     extra_paths: Vec<PathBuf>,
+    mode: ScanMode,
 ) -> Result<Report> {
-    let repos = config.scan_with_extra_paths(&extra_paths);
-    let mut report = Report::new(&repos);
+    let result = config.scan(&extra_paths, mode);
+    let mut report = Report::new(&result.repos);
     report.add_message(format!(
         "Found {} repos. Use `git global list` to show them.",
-        repos.len()
+        result.repos.len()
     ));
+    if config.verbose && mode == ScanMode::Incremental {
+        report.add_message(format!(
+            "Skipped {} directories that were unchanged since the last scan.",
+            result.unchanged_dirs
+        ));
+    }
+    add_repo_list(&mut report, "Added", &result.added);
+    add_repo_list(&mut report, "Removed", &result.removed);
+    add_errors(&mut report, &result.errors, config.verbose);
+    if let Some(e) = result.cache_error {
+        report.add_message(format!("Could not save the list of repos: {}", e));
+        report.mark_failed();
+    }
     Ok(report)
 }
+
+/// Summarizes the errors encountered while scanning, listing each of them if
+/// `verbose` is set. The JSON form of the report always lists them.
+fn add_errors(report: &mut Report, errors: &[ScanError], verbose: bool) {
+    report.add_data("scan_errors", json!(errors));
+    if errors.is_empty() {
+        return;
+    }
+    let mut counts = BTreeMap::new();
+    for error in errors {
+        *counts.entry(error.kind).or_insert(0) += 1;
+    }
+    let counts: Vec<String> = counts
+        .iter()
+        .map(|(kind, count)| format!("{} {}", count, kind))
+        .collect();
+    let noun = if errors.len() == 1 { "path" } else { "paths" };
+    report.add_message(format!(
+        "Could not scan {} {} ({}).",
+        errors.len(),
+        noun,
+        counts.join(", ")
+    ));
+    if verbose {
+        for error in errors {
+            report.add_message(format!("  {}", error.message));
+        }
+    } else {
+        report.add_message("Use --verbose to list them.".to_string());
+    }
+}
+
+/// Adds a heading and an indented line per repo, if there are any repos.
+fn add_repo_list(report: &mut Report, heading: &str, repos: &[Repo]) {
+    if repos.is_empty() {
+        return;
+    }
+    let noun = if repos.len() == 1 { "repo" } else { "repos" };
+    report.add_message(format!("{} {} {}:", heading, repos.len(), noun));
+    for repo in repos {
+        report.add_message(format!("  {}", repo));
+    }
+}
@@ -3,7 +3,7 @@
 use crate::config::Config;
 use crate::errors::Result;
 use crate::parallel::{default_parallelism, run_parallel};
-use crate::repo::Repo;
+use crate::repo::{Repo, RepoKind};
 use crate::report::Report;
 
 /// Runs the `stashed` subcommand.
@@ -13,13 +13,23 @@ pub fn execute(mut config: Config) -> Result<Report> {
     report.pad_repo_output();
 
     let results = run_parallel(repos, default_parallelism(), |repo| {
+        // A linked worktree shares its stashes with the main working tree, so
+        // they're only listed once, for the latter.
+        if repo.kind() == RepoKind::Worktree {
+            return Ok(vec![]);
+        }
         repo.get_stash_list()
     });
 
     for (path, stash) in results {
         let repo = Repo::new(path);
-        for line in stash {
-            report.add_repo_message(&repo, line);
+        match stash {
+            Ok(stash) => {
+                for line in stash {
+                    report.add_repo_message(&repo, line);
+                }
+            }
+            Err(e) => report.add_repo_error(&repo, &e),
         }
     }
 
@@ -15,6 +15,10 @@ pub fn execute(mut config: Config) -> Result<Report> {
     report.pad_repo_output();
 
     let results = run_parallel(repos, default_parallelism(), move |repo| {
+        // Bare repos have no working tree (or index) to report on.
+        if !repo.kind().has_work_tree() {
+            return Ok(vec![]);
+        }
         let mut status_opts = git2::StatusOptions::new();
         status_opts
             .show(git2::StatusShow::IndexAndWorkdir)
@@ -25,8 +29,13 @@ pub fn execute(mut config: Config) -> Result<Report> {
 
     for (path, lines) in results {
         let repo = Repo::new(path);
-        for line in lines {
-            report.add_repo_message(&repo, line);
+        match lines {
+            Ok(lines) => {
+                for line in lines {
+                    report.add_repo_message(&repo, line);
+                }
+            }
+            Err(e) => report.add_repo_error(&repo, &e),
         }
     }
 
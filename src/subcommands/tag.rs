@@ -0,0 +1,75 @@
+//! The `tag` subcommand: tags repos, to slice them into named groups.
+//!
+//! Repos can be given by path, or by glob pattern: a pattern with a `/` is
+//! matched against the full paths of the known repos, while one without is
+//! matched against just the names of their directories:
+//!
+//! ```bash
+//! $ git global tag work ~/work/api 'client-*'
+//! $ git global tag vendor '/home/me/src/vendor/**'
+//! ```
+//!
+//! Any subcommand can then be limited to the repos with a tag, or with any of
+//! several tags, using the global `--tag` (or `--group`) option:
+//!
+//! ```bash
+//! $ git global status --tag work
+//! ```
+//!
+//! Without arguments, `tag` lists each tag with the repos that have it.
+
+use crate::config::Config;
+use crate::errors::Result;
+use crate::report::Report;
+
+/// Tags the repos that each target refers to, or lists the tags, if no tag
+/// is given.
+pub fn execute(
+    mut config: Config,
+    tag: Option<String>,
+    targets: Vec<String>,
+) -> Result<Report> {
+    let mut report = Report::new(&[]);
+    let Some(tag) = tag else {
+        list_tags(&config, &mut report);
+        return Ok(report);
+    };
+    // Populate the cache first, if necessary, so there are repos to match.
+    config.refresh_cache();
+    let matches = match config.tag_repos(&tag, &targets) {
+        Ok(matches) => matches,
+        Err(e) => {
+            report.add_message(format!("Could not tag repos: {}", e));
+            report.mark_failed();
+            return Ok(report);
+        }
+    };
+    for (target, repos) in targets.iter().zip(matches) {
+        if repos.is_empty() {
+            report.add_message(format!("No known repos match {}.", target));
+        }
+        for repo in repos {
+            report.add_message(format!("Tagged {} with {}.", repo, tag));
+        }
+    }
+    Ok(report)
+}
+
+/// Adds each tag and its repos to the report.
+fn list_tags(config: &Config, report: &mut Report) {
+    let tags = config.get_tags();
+    if tags.is_empty() {
+        report.add_message(
+            "No repos are tagged. Use `git global tag <tag> <path>...` to tag \
+             some."
+                .to_string(),
+        );
+    }
+    for (tag, repos) in tags {
+        let noun = if repos.len() == 1 { "repo" } else { "repos" };
+        report.add_message(format!("{} ({} {}):", tag, repos.len(), noun));
+        for repo in repos {
+            report.add_message(format!("  {}", repo));
+        }
+    }
+}
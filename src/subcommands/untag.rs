@@ -0,0 +1,57 @@
+//! The `untag` subcommand: removes a tag from repos.
+//!
+//! Repos are given just as they are to `git global tag`. Without any, the
+//! tag is removed from every repo:
+//!
+//! ```bash
+//! $ git global untag work ~/work/old-api
+//! $ git global untag vendor
+//! ```
+
+use crate::config::Config;
+use crate::errors::Result;
+use crate::repo::Repo;
+use crate::report::Report;
+
+/// Removes a tag from the repos that each target refers to, or from every
+/// repo, if there are no targets.
+pub fn execute(
+    config: Config,
+    tag: &str,
+    targets: Vec<String>,
+) -> Result<Report> {
+    let mut report = Report::new(&[]);
+    let matches = match config.untag_repos(tag, &targets) {
+        Ok(matches) => matches,
+        Err(e) => {
+            report.add_message(format!("Could not untag repos: {}", e));
+            report.mark_failed();
+            return Ok(report);
+        }
+    };
+    if targets.is_empty() {
+        let repos = matches.into_iter().flatten().collect::<Vec<_>>();
+        if repos.is_empty() {
+            report.add_message(format!("No repos are tagged {}.", tag));
+        }
+        add_removals(&mut report, tag, repos);
+        return Ok(report);
+    }
+    for (target, repos) in targets.iter().zip(matches) {
+        if repos.is_empty() {
+            report.add_message(format!(
+                "No repos tagged {} match {}.",
+                tag, target
+            ));
+        }
+        add_removals(&mut report, tag, repos);
+    }
+    Ok(report)
+}
+
+/// Adds a line for each repo the tag was removed from.
+fn add_removals(report: &mut Report, tag: &str, repos: Vec<Repo>) {
+    for repo in repos {
+        report.add_message(format!("Removed {} from {}.", tag, repo));
+    }
+}
@@ -0,0 +1,352 @@
+//! The `watch` subcommand: keeps the cache of known repos current.
+//!
+//! `git global watch` scans the base directories once, then keeps running in
+//! the foreground, watching every directory the scan read for entries that
+//! are created, deleted or moved. When a repo is cloned, moved or deleted,
+//! only the directories involved are rescanned, and the cache is updated right
+//! away, so every other `git global` invocation sees the change without a
+//! scan of its own:
+//!
+//! ```bash
+//! $ git global watch
+//! Added /home/me/src/new-project.
+//! Removed /home/me/src/old-project.
+//! ```
+//!
+//! Each batch of changes is written as a report of its own, so with `--json`,
+//! a JSON object with the `added` and `removed` repos follows every change.
+//!
+//! Rescans follow the same rules as `git global scan`, including
+//! `global.ignore`, per-basedir settings, `global.same-filesystem` and
+//! `global.scan-nested`. While the watcher runs, the cache never goes stale,
+//! so `global.cache-ttl` doesn't trigger rescans either.
+//!
+//! Watching relies on Linux's inotify, which limits how many directories can
+//! be watched; see `fs.inotify.max_user_watches` if that limit is reached.
+
+use std::fmt;
+use std::io;
+
+use serde_json::json;
+
+use crate::config::Config;
+use crate::errors::{GitGlobalError, Result};
+use crate::repo::Repo;
+use crate::report::Report;
+
+#[cfg(target_os = "linux")]
+pub use self::linux::Watcher;
+
+/// A change to the set of known repos.
+#[derive(Eq, PartialEq)]
+pub enum Change {
+    Added(Repo),
+    Removed(Repo),
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::Added(repo) => write!(f, "Added {}.", repo),
+            Change::Removed(repo) => write!(f, "Removed {}.", repo),
+        }
+    }
+}
+
+/// Watches the base directories until interrupted, printing each change to
+/// the known repos as it happens. Only returns if watching fails.
+pub fn execute(config: Config) -> Result<Report> {
+    match watch(config) {
+        Ok(()) => Ok(Report::new(&[])),
+        Err(e) => Err(GitGlobalError::Failed(format!(
+            "Could not watch for repos: {}",
+            e
+        ))),
+    }
+}
+
+/// Returns a report of some changes to the known repos.
+pub fn report(changes: &[Change]) -> Report {
+    let mut report = Report::new(&[]);
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+    for change in changes {
+        report.add_message(change.to_string());
+        match change {
+            Change::Added(repo) => added.push(repo.path()),
+            Change::Removed(repo) => removed.push(repo.path()),
+        }
+    }
+    report.add_data("added", json!(added));
+    report.add_data("removed", json!(removed));
+    report
+}
+
+#[cfg(target_os = "linux")]
+fn watch(config: Config) -> io::Result<()> {
+    use std::io::Write;
+    use std::time::Duration;
+
+    use crate::progress::Progress;
+
+    /// How often to record that the cache is current, while nothing changes.
+    const HEARTBEAT: Duration = Duration::from_secs(60);
+
+    let json = config.json;
+    let print = |changes: Vec<Change>| {
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let mut stdout = io::stdout().lock();
+        report(&changes).write(&mut stdout, json);
+        stdout.flush()
+    };
+    let mut progress = Progress::new(config.quiet);
+    for basedir in &config.basedirs {
+        progress.message(format!(
+            "Scanning for git repos under {}; this may take a while...",
+            basedir.path.display()
+        ));
+    }
+    let mut watcher = Watcher::new(config)?;
+    print(watcher.start()?)?;
+    progress.message(format!(
+        "Watching {} directories for changes; press Ctrl-C to stop.",
+        watcher.n_watched()
+    ));
+    loop {
+        print(watcher.wait(HEARTBEAT)?)?;
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn watch(_: Config) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "watching is only supported on Linux",
+    ))
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::collections::{BTreeMap, HashMap, HashSet};
+    use std::io;
+    use std::path::{Path, PathBuf};
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+
+    use super::Change;
+    use crate::config::Config;
+    use crate::inotify::{
+        Event, IN_CREATE, IN_DELETE, IN_IGNORED, IN_ISDIR, IN_MOVED_FROM,
+        IN_MOVED_TO, IN_ONLYDIR, IN_Q_OVERFLOW, Inotify, WatchId,
+    };
+    use crate::walk::{self, Child, ReadDir};
+
+    /// The events that can mean a repo appeared or disappeared.
+    const WATCH_MASK: u32 =
+        IN_CREATE | IN_DELETE | IN_MOVED_FROM | IN_MOVED_TO | IN_ONLYDIR;
+
+    /// How long the filesystem has to be quiet before a burst of events,
+    /// like those of a clone, is acted on.
+    const SETTLE_TIME: Duration = Duration::from_millis(100);
+
+    /// The longest a steady stream of events can put off acting on them.
+    const MAX_DELAY: Duration = Duration::from_secs(2);
+
+    /// A `ReadDir` that remembers which directories it read.
+    #[derive(Default)]
+    struct Tracker {
+        dirs: Mutex<Vec<PathBuf>>,
+    }
+
+    impl ReadDir for Tracker {
+        fn read_dir(&self, dir: &Path) -> io::Result<Vec<Child>> {
+            let children = walk::read_dir(dir)?;
+            self.dirs.lock().unwrap().push(dir.to_path_buf());
+            Ok(children)
+        }
+    }
+
+    /// Keeps the cache current by watching every directory that a scan of
+    /// the base directories reads, and rescanning just the parts of the tree
+    /// that change.
+    pub struct Watcher {
+        config: Config,
+        inotify: Inotify,
+        /// The watched directories, by watch descriptor.
+        dirs: HashMap<WatchId, PathBuf>,
+        /// The watch descriptors, by directory.
+        watches: BTreeMap<PathBuf, WatchId>,
+    }
+
+    impl Watcher {
+        /// Create a new `Watcher`, which doesn't watch anything until it's
+        /// started.
+        pub fn new(config: Config) -> io::Result<Watcher> {
+            Ok(Watcher {
+                config,
+                inotify: Inotify::new()?,
+                dirs: HashMap::new(),
+                watches: BTreeMap::new(),
+            })
+        }
+
+        /// Returns the number of directories being watched.
+        pub fn n_watched(&self) -> usize {
+            self.watches.len()
+        }
+
+        /// Scans the base directories, updating the cache, and starts
+        /// watching them. Returns the changes since the cache was last
+        /// updated.
+        pub fn start(&mut self) -> io::Result<Vec<Change>> {
+            let roots = self.basedir_paths();
+            self.rescan(roots)
+        }
+
+        /// Waits up to `timeout` for something to change, then updates the
+        /// cache, returning the changes to the known repos, if any.
+        pub fn wait(&mut self, timeout: Duration) -> io::Result<Vec<Change>> {
+            let mut events = self.inotify.read_events(Some(timeout))?;
+            let start = Instant::now();
+            while !events.is_empty() && start.elapsed() < MAX_DELAY {
+                let more = self.inotify.read_events(Some(SETTLE_TIME))?;
+                if more.is_empty() {
+                    break;
+                }
+                events.extend(more);
+            }
+            let targets = self.targets(&events);
+            self.rescan(targets)
+        }
+
+        fn basedir_paths(&self) -> Vec<PathBuf> {
+            self.config
+                .basedirs
+                .iter()
+                .map(|basedir| basedir.path.clone())
+                .collect()
+        }
+
+        /// Returns the paths that need to be rescanned because of some
+        /// events, leaving out any that are inside of others.
+        fn targets(&mut self, events: &[Event]) -> Vec<PathBuf> {
+            let mut targets = Vec::new();
+            for event in events {
+                if event.mask & IN_Q_OVERFLOW != 0 {
+                    // Events were lost, so anything could have changed.
+                    targets.extend(self.basedir_paths());
+                    continue;
+                }
+                let Some(dir) = self.dirs.get(&event.wd).cloned() else {
+                    continue;
+                };
+                if event.mask & IN_IGNORED != 0 {
+                    // The directory is gone, and so is its watch.
+                    self.dirs.remove(&event.wd);
+                    if self.watches.get(&dir) == Some(&event.wd) {
+                        self.watches.remove(&dir);
+                    }
+                    continue;
+                }
+                let Some(name) = &event.name else {
+                    continue;
+                };
+                if let Some(repo) = git_dir_owner(&dir) {
+                    // A git directory is only watched while it's incomplete,
+                    // e.g., while it's being cloned into.
+                    targets.push(repo);
+                } else if name == ".git" || name == "HEAD" {
+                    targets.push(dir);
+                } else if event.mask & IN_ISDIR != 0
+                    || self.config.follow_symlinks
+                {
+                    // Symbolic links to directories may lead to repos, too.
+                    targets.push(dir.join(name));
+                }
+            }
+            targets.sort();
+            targets.dedup();
+            let mut minimal: Vec<PathBuf> = Vec::new();
+            for target in targets {
+                if !minimal.iter().any(|t| target.starts_with(t)) {
+                    minimal.push(target);
+                }
+            }
+            minimal
+        }
+
+        /// Rescans each of the given paths, updating the cache and which
+        /// directories are watched.
+        fn rescan(&mut self, targets: Vec<PathBuf>) -> io::Result<Vec<Change>> {
+            let mut changes = Vec::new();
+            for target in targets {
+                let tracker = Tracker::default();
+                let result = self.config.scan_subtree(&tracker, &target);
+                if let Some(e) = result.cache_error {
+                    return Err(io::Error::other(e));
+                }
+                changes.extend(result.removed.into_iter().map(Change::Removed));
+                changes.extend(result.added.into_iter().map(Change::Added));
+                let read: HashSet<PathBuf> =
+                    tracker.dirs.into_inner().unwrap().into_iter().collect();
+                let stale: Vec<(PathBuf, WatchId)> = self
+                    .watches
+                    .range(target.clone()..)
+                    .take_while(|(path, _)| path.starts_with(&target))
+                    .filter(|(path, _)| !read.contains(*path))
+                    .map(|(path, wd)| (path.clone(), *wd))
+                    .collect();
+                for (path, wd) in stale {
+                    self.inotify.rm_watch(wd);
+                    self.watches.remove(&path);
+                    self.dirs.remove(&wd);
+                }
+                for dir in read {
+                    if !self.watches.contains_key(&dir) {
+                        self.watch(dir)?;
+                    }
+                }
+            }
+            // Everything that could have changed has been rescanned.
+            if let Err(e) = self.config.mark_cache_fresh() {
+                return Err(io::Error::other(e));
+            }
+            Ok(changes)
+        }
+
+        fn watch(&mut self, dir: PathBuf) -> io::Result<()> {
+            match self.inotify.add_watch(&dir, WATCH_MASK) {
+                Ok(wd) => {
+                    self.dirs.insert(wd, dir.clone());
+                    self.watches.insert(dir, wd);
+                    Ok(())
+                }
+                Err(e) if e.raw_os_error() == Some(libc::ENOSPC) => {
+                    Err(io::Error::other(format!(
+                        "could not watch {}: too many directories; raise \
+                         the fs.inotify.max_user_watches sysctl to watch \
+                         more",
+                        dir.display()
+                    )))
+                }
+                // The directory was removed since it was read, which the
+                // watch on its parent will tell of.
+                Err(_) => Ok(()),
+            }
+        }
+    }
+
+    /// Returns the repo whose git directory contains the given path, if any.
+    fn git_dir_owner(path: &Path) -> Option<PathBuf> {
+        let mut owner = PathBuf::new();
+        for component in path.components() {
+            if component.as_os_str() == ".git" {
+                return Some(owner);
+            }
+            owner.push(component);
+        }
+        None
+    }
+}
@@ -37,6 +37,37 @@ impl TestEnv {
         }
     }
 
+    pub fn create_bare_repo(&mut self, name: &str) -> &mut Self {
+        git2::Repository::init_bare(self.tempdir.path().join(name)).unwrap();
+        self
+    }
+
+    /// Creates a repo whose git directory lives apart from its working tree,
+    /// as with `git init --separate-git-dir`.
+    pub fn create_repo_with_separate_git_dir(
+        &mut self,
+        name: &str,
+        git_dir: &str,
+    ) -> RepoBuilder<'_> {
+        let repo_path = self.tempdir.path().join(name);
+        let mut opts = git2::RepositoryInitOptions::new();
+        opts.initial_head("master")
+            .no_dotgit_dir(true)
+            .workdir_path(&repo_path);
+        let repo = git2::Repository::init_opts(
+            self.tempdir.path().join(git_dir),
+            &opts,
+        )
+        .unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "Test User").unwrap();
+        config.set_str("user.email", "test@example.com").unwrap();
+        RepoBuilder {
+            env: self,
+            repo_path,
+        }
+    }
+
     pub fn write_gitconfig(&self) -> PathBuf {
         let gitconfig_path = self.tempdir.path().join(".gitconfig");
         let mut f = File::create(&gitconfig_path).unwrap();
@@ -48,7 +79,7 @@ impl TestEnv {
     pub fn config(&self) -> Config {
         let gitconfig_path = self.write_gitconfig();
         let mut config = Config::from_gitconfig(&gitconfig_path);
-        config.cache_file = Some(self.tempdir.path().join("repos.txt"));
+        config.cache_file = Some(self.tempdir.path().join("repos.json"));
         config.manpage_file = None;
         config
     }
@@ -108,12 +139,11 @@ impl<'a> RepoBuilder<'a> {
         self
     }
 
+    /// Adds a bare `origin` remote next to the repo and pushes `master` to
+    /// it, setting it as the upstream, as `git push -u` does.
     pub fn setup_remote(self) -> Self {
         let repo = git2::Repository::open(&self.repo_path).unwrap();
-        let remote_path = self.env.tempdir.path().join(format!(
-            "{}.git",
-            self.repo_path.file_name().unwrap().to_str().unwrap()
-        ));
+        let remote_path = self.remote_path();
         git2::Repository::init_bare(&remote_path).unwrap();
         repo.remote("origin", remote_path.to_str().unwrap())
             .unwrap();
@@ -121,6 +151,81 @@ impl<'a> RepoBuilder<'a> {
         remote
             .push(&["refs/heads/master:refs/heads/master"], None)
             .unwrap();
+        repo.find_branch("master", git2::BranchType::Local)
+            .unwrap()
+            .set_upstream(Some("origin/master"))
+            .unwrap();
+        self
+    }
+
+    /// Commits a file to `master` on the remote made by `setup_remote`, as
+    /// if someone else had pushed it. The repo doesn't see the commit until
+    /// it fetches.
+    pub fn remote_commit(self, filename: &str, content: &str) -> Self {
+        let remote_path = self.remote_path();
+        let remote = git2::Repository::open_bare(remote_path).unwrap();
+        let parent = remote
+            .find_reference("refs/heads/master")
+            .unwrap()
+            .peel_to_commit()
+            .unwrap();
+        let blob = remote.blob(content.as_bytes()).unwrap();
+        let mut builder =
+            remote.treebuilder(Some(&parent.tree().unwrap())).unwrap();
+        builder.insert(filename, blob, 0o100644).unwrap();
+        let tree = remote.find_tree(builder.write().unwrap()).unwrap();
+        let sig =
+            git2::Signature::now("Someone Else", "else@example.com").unwrap();
+        remote
+            .commit(
+                Some("refs/heads/master"),
+                &sig,
+                &sig,
+                "remote commit",
+                &tree,
+                &[&parent],
+            )
+            .unwrap();
+        self
+    }
+
+    /// Fetches from the `origin` remote, updating its remote-tracking
+    /// branches.
+    pub fn fetch(self) -> Self {
+        let repo = git2::Repository::open(&self.repo_path).unwrap();
+        repo.find_remote("origin")
+            .unwrap()
+            .fetch(&[] as &[&str], None, None)
+            .unwrap();
+        self
+    }
+
+    fn remote_path(&self) -> PathBuf {
+        self.env.tempdir.path().join(format!(
+            "{}.git",
+            self.repo_path.file_name().unwrap().to_str().unwrap()
+        ))
+    }
+
+    /// Adds a linked worktree (as with `git worktree add`) with the given
+    /// name, next to the repo.
+    pub fn worktree(self, name: &str) -> Self {
+        let repo = git2::Repository::open(&self.repo_path).unwrap();
+        repo.worktree(name, &self.env.tempdir.path().join(name), None)
+            .unwrap();
+        self
+    }
+
+    /// Adds the named repo from the test environment as a submodule at the
+    /// given path.
+    pub fn submodule(self, name: &str, path: &str) -> Self {
+        let repo = git2::Repository::open(&self.repo_path).unwrap();
+        let url = self.env.tempdir.path().join(name);
+        let mut module = repo
+            .submodule(url.to_str().unwrap(), std::path::Path::new(path), true)
+            .unwrap();
+        module.clone(None).unwrap();
+        module.add_finalize().unwrap();
         self
     }
 
@@ -149,10 +254,10 @@ mod tests {
             .build();
 
         let config = env.config();
-        assert_eq!(config.basedir, env.tempdir.path());
-        // Verify that we can find the repos
+        assert_eq!(config.basedirs[0].path, env.tempdir.path());
+        // Verify that we can find the repos, including repo1's bare remote.
         let mut config = config;
         let repos = config.get_repos();
-        assert_eq!(repos.len(), 3);
+        assert_eq!(repos.len(), 4);
     }
 }
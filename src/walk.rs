@@ -0,0 +1,719 @@
+//! Parallel directory walking for git-global's repo discovery.
+//!
+//! Provides a `Walker` that traverses a directory tree across a pool of
+//! threads. Each thread owns a queue of directories to read; idle threads
+//! steal work from the others, so one huge subtree doesn't leave the rest of
+//! the pool waiting. The options mirror the subset of `walkdir::WalkDir` that
+//! git-global relies on: following symbolic links (with loop detection),
+//! staying on the root's filesystem, and limiting the depth of the walk.
+//!
+//! Directories are listed through the `ReadDir` trait, so callers can serve
+//! listings from somewhere other than the filesystem (see the `snapshot`
+//! module). That's why this isn't built on a walking crate like `ignore`:
+//! none of them let an incremental scan skip reading unchanged directories.
+
+use std::collections::VecDeque;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::fs::{self, FileType};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+
+use serde::{Deserialize, Serialize};
+
+/// What a walker should do after visiting an entry.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WalkState {
+    /// Descend into the entry, if it's a directory.
+    Continue,
+    /// Don't descend into the entry.
+    Skip,
+    /// Don't descend into the entry, nor visit or descend into any of the
+    /// entries of its directory that haven't been visited yet.
+    SkipSiblings,
+}
+
+/// The kind of a directory entry.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileKind {
+    Dir,
+    File,
+    Symlink,
+}
+
+impl From<FileType> for FileKind {
+    fn from(file_type: FileType) -> FileKind {
+        if file_type.is_symlink() {
+            FileKind::Symlink
+        } else if file_type.is_dir() {
+            FileKind::Dir
+        } else {
+            FileKind::File
+        }
+    }
+}
+
+/// An entry in a directory listing, as returned by `ReadDir`.
+#[derive(Clone, Debug)]
+pub struct Child {
+    pub name: OsString,
+    pub kind: FileKind,
+}
+
+/// Lists the contents of directories for a `Walker`.
+pub trait ReadDir: Sync {
+    /// Returns the entries of the given directory.
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<Child>>;
+}
+
+/// Lists the contents of a directory from the filesystem.
+///
+/// An error reading any one entry is returned as an error for the whole
+/// directory.
+pub fn read_dir(dir: &Path) -> io::Result<Vec<Child>> {
+    fs::read_dir(dir)?
+        .map(|entry| {
+            let entry = entry?;
+            Ok(Child {
+                kind: entry.file_type()?.into(),
+                name: entry.file_name(),
+            })
+        })
+        .collect()
+}
+
+/// A file or directory found while walking.
+#[derive(Clone, Debug)]
+pub struct DirEntry {
+    path: PathBuf,
+    kind: FileKind,
+    follow_link: bool,
+}
+
+impl DirEntry {
+    /// Returns the full path to the entry.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the entry's file name (or the full path, for a root).
+    pub fn file_name(&self) -> &OsStr {
+        self.path.file_name().unwrap_or(self.path.as_os_str())
+    }
+
+    /// Returns `true` if the entry is a directory, or a symbolic link to one
+    /// that is being followed.
+    pub fn is_dir(&self) -> bool {
+        self.kind == FileKind::Dir
+    }
+}
+
+/// An error encountered while walking.
+#[derive(Debug)]
+pub struct Error {
+    path: PathBuf,
+    kind: ErrorKind,
+}
+
+#[derive(Debug)]
+enum ErrorKind {
+    Io(io::Error),
+    Loop { ancestor: PathBuf },
+}
+
+impl Error {
+    fn io(path: &Path, err: io::Error) -> Error {
+        Error {
+            path: path.to_path_buf(),
+            kind: ErrorKind::Io(err),
+        }
+    }
+
+    /// Returns the path of the entry that caused the error.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns `true` if the error is a symbolic link back to one of its
+    /// ancestors.
+    pub fn is_loop(&self) -> bool {
+        matches!(self.kind, ErrorKind::Loop { .. })
+    }
+
+    /// Returns the underlying I/O error, if there is one.
+    pub fn io_error(&self) -> Option<&io::Error> {
+        match self.kind {
+            ErrorKind::Io(ref err) => Some(err),
+            ErrorKind::Loop {
+                ..
+            } => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ErrorKind::Io(ref err) => {
+                write!(f, "{}: {}", self.path.display(), err)
+            }
+            ErrorKind::Loop {
+                ref ancestor,
+            } => write!(
+                f,
+                "{}: symbolic link loop back to {}",
+                self.path.display(),
+                ancestor.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A directory waiting to be read.
+struct Work {
+    path: PathBuf,
+    /// The canonical path of the directory, tracked only when following
+    /// symbolic links, for loop detection.
+    real: Option<PathBuf>,
+    /// How many directories below the root this one is.
+    depth: usize,
+}
+
+/// A parallel directory walker.
+pub struct Walker {
+    root: PathBuf,
+    follow_links: bool,
+    same_file_system: bool,
+    max_depth: Option<usize>,
+    threads: usize,
+}
+
+impl Walker {
+    /// Create a new `Walker` rooted at the given path.
+    pub fn new<P: Into<PathBuf>>(root: P) -> Walker {
+        Walker {
+            root: root.into(),
+            follow_links: false,
+            same_file_system: false,
+            max_depth: None,
+            threads: 1,
+        }
+    }
+
+    /// Whether to follow symbolic links to directories.
+    pub fn follow_links(mut self, yes: bool) -> Walker {
+        self.follow_links = yes;
+        self
+    }
+
+    /// Whether to avoid descending into directories on other filesystems.
+    pub fn same_file_system(mut self, yes: bool) -> Walker {
+        self.same_file_system = yes;
+        self
+    }
+
+    /// The maximum depth of entries to visit. The root is at depth 0, its
+    /// entries are at depth 1, and so on.
+    pub fn max_depth(mut self, depth: usize) -> Walker {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// The number of threads to walk with.
+    pub fn threads(mut self, n: usize) -> Walker {
+        self.threads = n.max(1);
+        self
+    }
+
+    /// Walks the tree, listing directories with `read_dir` and calling
+    /// `visit` for every entry and error.
+    ///
+    /// Entries that don't pass `filter` are neither visited nor descended
+    /// into. The filter sees symbolic links as they are, before they're
+    /// followed. Directories are only descended into if `visit` returns
+    /// `WalkState::Continue` for them, and only once every entry of their
+    /// parent directory has been visited. Entries are visited in no
+    /// particular order, from multiple threads at once.
+    pub fn run<R, F, V>(self, read_dir: &R, filter: F, visit: V)
+    where
+        R: ReadDir,
+        F: Fn(&DirEntry) -> bool + Sync,
+        V: Fn(Result<&DirEntry, Error>) -> WalkState + Sync,
+    {
+        // The root is always followed if it's a symbolic link.
+        let root = match fs::metadata(&self.root) {
+            Ok(md) => DirEntry {
+                path: self.root.clone(),
+                kind: md.file_type().into(),
+                follow_link: false,
+            },
+            Err(err) => {
+                visit(Err(Error::io(&self.root, err)));
+                return;
+            }
+        };
+        if !filter(&root) {
+            return;
+        }
+        if visit(Ok(&root)) != WalkState::Continue
+            || !root.is_dir()
+            || self.max_depth == Some(0)
+        {
+            return;
+        }
+        let root_device = if self.same_file_system {
+            match device_num(&self.root) {
+                Ok(dev) => Some(dev),
+                Err(err) => {
+                    visit(Err(Error::io(&self.root, err)));
+                    return;
+                }
+            }
+        } else {
+            None
+        };
+        let real = if self.follow_links {
+            match fs::canonicalize(&self.root) {
+                Ok(real) => Some(real),
+                Err(err) => {
+                    visit(Err(Error::io(&self.root, err)));
+                    return;
+                }
+            }
+        } else {
+            None
+        };
+        let pool = Pool {
+            walker: &self,
+            read_dir,
+            root_device,
+            queues: (0..self.threads)
+                .map(|_| Mutex::new(VecDeque::new()))
+                .collect(),
+            pending: AtomicUsize::new(0),
+            idle: (Mutex::new(()), Condvar::new()),
+        };
+        pool.push(
+            0,
+            Work {
+                path: self.root.clone(),
+                real,
+                depth: 0,
+            },
+        );
+        thread::scope(|scope| {
+            for id in 0..self.threads {
+                let pool = &pool;
+                let filter = &filter;
+                let visit = &visit;
+                scope.spawn(move || pool.work(id, filter, visit));
+            }
+        });
+    }
+}
+
+/// Shared state for the threads of a single walk.
+struct Pool<'a, R> {
+    walker: &'a Walker,
+    read_dir: &'a R,
+    root_device: Option<u64>,
+    queues: Vec<Mutex<VecDeque<Work>>>,
+    /// Directories that are queued or being read.
+    pending: AtomicUsize,
+    /// Idle threads park on the condvar until there's work to steal, or the
+    /// walk is over. It's notified with the mutex held, so that a thread
+    /// can't miss a wakeup between finding nothing to do and parking.
+    idle: (Mutex<()>, Condvar),
+}
+
+impl<R: ReadDir> Pool<'_, R> {
+    /// Queues a directory on the given thread's queue.
+    fn push(&self, id: usize, work: Work) {
+        self.pending.fetch_add(1, Ordering::SeqCst);
+        self.queues[id].lock().unwrap().push_back(work);
+    }
+
+    /// Wakes the idle threads.
+    fn wake(&self) {
+        let _guard = self.idle.0.lock().unwrap();
+        self.idle.1.notify_all();
+    }
+
+    /// Returns `true` if any thread has queued work.
+    fn has_work(&self) -> bool {
+        self.queues
+            .iter()
+            .any(|queue| !queue.lock().unwrap().is_empty())
+    }
+
+    /// Takes the newest work from our own queue, or else the oldest work from
+    /// another thread's queue.
+    fn pop(&self, id: usize) -> Option<Work> {
+        if let Some(work) = self.queues[id].lock().unwrap().pop_back() {
+            return Some(work);
+        }
+        let n = self.queues.len();
+        (1..n)
+            .find_map(|i| self.queues[(id + i) % n].lock().unwrap().pop_front())
+    }
+
+    /// Runs one worker thread until there are no more directories to read.
+    fn work<F, V>(&self, id: usize, filter: &F, visit: &V)
+    where
+        F: Fn(&DirEntry) -> bool + Sync,
+        V: Fn(Result<&DirEntry, Error>) -> WalkState + Sync,
+    {
+        loop {
+            match self.pop(id) {
+                Some(work) => {
+                    let queued = self.read_dir(id, work, filter, visit);
+                    let done = self.pending.fetch_sub(1, Ordering::SeqCst) == 1;
+                    if queued || done {
+                        self.wake();
+                    }
+                }
+                None => {
+                    let guard = self.idle.0.lock().unwrap();
+                    if self.pending.load(Ordering::SeqCst) == 0 {
+                        break;
+                    }
+                    // Work that was queued before we took the lock would
+                    // otherwise go unnoticed until the next wakeup.
+                    if !self.has_work() {
+                        drop(self.idle.1.wait(guard).unwrap());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reads one directory, visiting its entries and then queueing its
+    /// subdirectories. Returns `true` if anything was queued.
+    fn read_dir<F, V>(
+        &self,
+        id: usize,
+        work: Work,
+        filter: &F,
+        visit: &V,
+    ) -> bool
+    where
+        F: Fn(&DirEntry) -> bool + Sync,
+        V: Fn(Result<&DirEntry, Error>) -> WalkState + Sync,
+    {
+        let children = match self.read_dir.read_dir(&work.path) {
+            Ok(children) => children,
+            Err(err) => {
+                visit(Err(Error::io(&work.path, err)));
+                return false;
+            }
+        };
+        let mut subdirs = Vec::new();
+        for child in children {
+            let mut dent = DirEntry {
+                path: work.path.join(&child.name),
+                kind: child.kind,
+                follow_link: false,
+            };
+            if !filter(&dent) {
+                continue;
+            }
+            if dent.kind == FileKind::Symlink && self.walker.follow_links {
+                match fs::metadata(&dent.path) {
+                    Ok(md) => {
+                        dent.kind = md.file_type().into();
+                        dent.follow_link = true;
+                    }
+                    Err(err) => {
+                        visit(Err(Error::io(&dent.path, err)));
+                        continue;
+                    }
+                }
+            }
+            match visit(Ok(&dent)) {
+                WalkState::SkipSiblings => return false,
+                WalkState::Skip => continue,
+                WalkState::Continue if !dent.is_dir() => continue,
+                WalkState::Continue => (),
+            }
+            // Don't read a directory whose entries would be too deep.
+            if self
+                .walker
+                .max_depth
+                .is_some_and(|max| work.depth + 1 >= max)
+            {
+                continue;
+            }
+            let real = match self.real_path(&work, &dent) {
+                Ok(real) => real,
+                Err(err) => {
+                    visit(Err(err));
+                    continue;
+                }
+            };
+            if let Some(root_device) = self.root_device {
+                match device_num(&dent.path) {
+                    Ok(dev) if dev != root_device => continue,
+                    Ok(_) => (),
+                    Err(err) => {
+                        visit(Err(Error::io(&dent.path, err)));
+                        continue;
+                    }
+                }
+            }
+            subdirs.push(Work {
+                path: dent.path,
+                real,
+                depth: work.depth + 1,
+            });
+        }
+        let queued = !subdirs.is_empty();
+        for work in subdirs {
+            self.push(id, work);
+        }
+        queued
+    }
+
+    /// Returns the canonical path of a subdirectory when following symbolic
+    /// links, or an error if it's a link back to one of its ancestors.
+    fn real_path(
+        &self,
+        parent: &Work,
+        dent: &DirEntry,
+    ) -> Result<Option<PathBuf>, Error> {
+        let Some(parent_real) = &parent.real else {
+            return Ok(None);
+        };
+        if !dent.follow_link {
+            return Ok(Some(parent_real.join(dent.file_name())));
+        }
+        let target = fs::canonicalize(&dent.path)
+            .map_err(|err| Error::io(&dent.path, err))?;
+        if parent_real.starts_with(&target) {
+            return Err(Error {
+                path: dent.path.clone(),
+                kind: ErrorKind::Loop {
+                    ancestor: target,
+                },
+            });
+        }
+        Ok(Some(target))
+    }
+}
+
+/// Returns an identifier for the filesystem that the path lives on.
+#[cfg(unix)]
+pub fn device_num(path: &Path) -> io::Result<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    path.metadata().map(|md| md.dev())
+}
+
+/// Returns an identifier for the filesystem that the path lives on.
+#[cfg(windows)]
+pub fn device_num(path: &Path) -> io::Result<u64> {
+    use winapi_util::{Handle, file};
+
+    let h = Handle::from_path_any(path)?;
+    file::information(h).map(|info| info.volume_serial_number())
+}
+
+/// Returns an identifier for the filesystem that the path lives on.
+#[cfg(not(any(unix, windows)))]
+pub fn device_num(_: &Path) -> io::Result<u64> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "same-filesystem is not supported on this platform",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{File, create_dir_all};
+    use std::sync::Mutex;
+
+    use tempfile::TempDir;
+
+    struct FsReadDir;
+
+    impl ReadDir for FsReadDir {
+        fn read_dir(&self, dir: &Path) -> io::Result<Vec<Child>> {
+            read_dir(dir)
+        }
+    }
+
+    fn walk_dirs(
+        walker: Walker,
+        filter: impl Fn(&DirEntry) -> bool + Sync,
+    ) -> Vec<PathBuf> {
+        let found = Mutex::new(Vec::new());
+        walker.run(&FsReadDir, filter, |entry| {
+            if let Ok(entry) = entry
+                && entry.is_dir()
+            {
+                found.lock().unwrap().push(entry.path().to_path_buf());
+            }
+            WalkState::Continue
+        });
+        let mut found = found.into_inner().unwrap();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn test_walks_all_directories() {
+        let tempdir = TempDir::new().unwrap();
+        let root = tempdir.path();
+        for dir in ["a/b/c", "a/d", "e"] {
+            create_dir_all(root.join(dir)).unwrap();
+        }
+        File::create(root.join("a/file.txt")).unwrap();
+
+        let found = walk_dirs(Walker::new(root).threads(4), |_| true);
+        let expected: Vec<PathBuf> = ["", "a", "a/b", "a/b/c", "a/d", "e"]
+            .iter()
+            .map(|d| {
+                if d.is_empty() {
+                    root.to_path_buf()
+                } else {
+                    root.join(d)
+                }
+            })
+            .collect();
+        assert_eq!(found, expected);
+    }
+
+    /// A tree of directories, three wide and three deep, that's slow to
+    /// read, so that threads run out of work while others are reading.
+    struct SlowReadDir;
+
+    impl ReadDir for SlowReadDir {
+        fn read_dir(&self, dir: &Path) -> io::Result<Vec<Child>> {
+            thread::sleep(std::time::Duration::from_millis(5));
+            if dir.components().count() > 3 {
+                return Ok(vec![]);
+            }
+            Ok(["x", "y", "z"]
+                .iter()
+                .map(|name| Child {
+                    name: name.into(),
+                    kind: FileKind::Dir,
+                })
+                .collect())
+        }
+    }
+
+    #[test]
+    fn test_idle_threads_wait_for_work() {
+        for _ in 0..5 {
+            let found = Mutex::new(0);
+            Walker::new("/").threads(8).run(
+                &SlowReadDir,
+                |_| true,
+                |entry| {
+                    assert!(entry.is_ok());
+                    *found.lock().unwrap() += 1;
+                    WalkState::Continue
+                },
+            );
+            assert_eq!(found.into_inner().unwrap(), 1 + 3 + 9 + 27);
+        }
+    }
+
+    #[test]
+    fn test_filter_prunes_subtrees() {
+        let tempdir = TempDir::new().unwrap();
+        let root = tempdir.path();
+        for dir in ["keep/inner", "skip/inner"] {
+            create_dir_all(root.join(dir)).unwrap();
+        }
+
+        let found = walk_dirs(Walker::new(root).threads(2), |e| {
+            !e.path().ends_with("skip")
+        });
+        assert_eq!(
+            found,
+            vec![
+                root.to_path_buf(),
+                root.join("keep"),
+                root.join("keep/inner")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_max_depth_limits_walk() {
+        let tempdir = TempDir::new().unwrap();
+        let root = tempdir.path();
+        create_dir_all(root.join("a/b/c")).unwrap();
+
+        let found = walk_dirs(Walker::new(root).max_depth(2), |_| true);
+        assert_eq!(
+            found,
+            vec![root.to_path_buf(), root.join("a"), root.join("a/b")]
+        );
+    }
+
+    #[test]
+    fn test_skip_siblings_prunes_directory() {
+        let tempdir = TempDir::new().unwrap();
+        let root = tempdir.path();
+        for dir in ["repo/src", "repo/target/debug", "other/inner"] {
+            create_dir_all(root.join(dir)).unwrap();
+        }
+        File::create(root.join("repo/marker")).unwrap();
+
+        let found = Mutex::new(Vec::new());
+        Walker::new(root).threads(2).run(
+            &FsReadDir,
+            |_| true,
+            |entry| {
+                let entry = entry.unwrap();
+                if entry.file_name() == "marker" {
+                    return WalkState::SkipSiblings;
+                }
+                if entry.is_dir() {
+                    found.lock().unwrap().push(entry.path().to_path_buf());
+                }
+                WalkState::Continue
+            },
+        );
+        // Subdirectories of `repo` may have been visited before the marker,
+        // but none of them are descended into.
+        let found = found.into_inner().unwrap();
+        assert!(found.contains(&root.join("other/inner")));
+        assert!(!found.contains(&root.join("repo/target/debug")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_loop_is_an_error() {
+        let tempdir = TempDir::new().unwrap();
+        let root = tempdir.path();
+        create_dir_all(root.join("a")).unwrap();
+        std::os::unix::fs::symlink(root, root.join("a/loop")).unwrap();
+
+        let errors = Mutex::new(Vec::new());
+        Walker::new(root).follow_links(true).threads(2).run(
+            &FsReadDir,
+            |_| true,
+            |entry| {
+                if let Err(err) = entry {
+                    errors.lock().unwrap().push(err);
+                }
+                WalkState::Continue
+            },
+        );
+        let errors = errors.into_inner().unwrap();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].is_loop());
+        assert_eq!(errors[0].path(), root.join("a/loop"));
+        assert!(errors[0].to_string().contains("symbolic link loop"));
+    }
+}
@@ -1,5 +1,57 @@
+use std::fs::File;
+use std::process::{Command, Output};
+
+use git_global::test_utils::TestEnv;
+
 #[test]
 fn verify_cli() {
     let app = git_global::get_clap_app();
     app.debug_assert();
 }
+
+#[test]
+fn test_output_streams() {
+    let mut env = TestEnv::new();
+    env.create_repo("a").commit("file.txt", "hello\n").build();
+    let gitconfig = env.write_gitconfig();
+    // A cache directory that can't be created, so that there's a warning.
+    let cache_home = env.tempdir.path().join("not-a-dir");
+    File::create(&cache_home).unwrap();
+    let run = |args: &[&str]| -> Output {
+        Command::new(env!("CARGO_BIN_EXE_git-global"))
+            .arg("--config")
+            .arg(&gitconfig)
+            .args(args)
+            .env("HOME", env.tempdir.path())
+            .env("XDG_CACHE_HOME", &cache_home)
+            .output()
+            .unwrap()
+    };
+    let repo = format!("{}\n", env.tempdir.path().join("a").display());
+
+    // Results go to stdout, and warnings to stderr.
+    let output = run(&["list"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), repo);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.starts_with("warning: Could not save the list of repos: "),
+        "{}",
+        stderr
+    );
+
+    // With --quiet, only the results are printed.
+    let output = run(&["--quiet", "list"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), repo);
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+
+    // Errors go to stderr, even with --quiet.
+    let output = run(&["--quiet", "grep", "("]);
+    assert!(!output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .starts_with("Invalid pattern: ")
+    );
+}
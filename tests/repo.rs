@@ -1,11 +1,60 @@
 mod utils;
 
+use git_global::test_utils::TestEnv;
+use git_global::{RepoKind, ScanMode};
+
 #[test]
 /// Test that we get an actual git repo, we can get a git2::Repository
 /// reference to it, and it's not bare.
 fn test_repo_initialization() {
     utils::with_temp_repo(|repo| {
-        let git2_repo = repo.as_git2_repo();
+        let git2_repo = repo.as_git2_repo().unwrap();
         assert!(!git2_repo.is_bare());
     });
 }
+
+#[test]
+/// Test that scans find every kind of repo, and tell them apart.
+fn test_scan_finds_each_kind_of_repo() {
+    let mut env = TestEnv::new();
+    env.create_repo("main")
+        .commit("file.txt", "content")
+        .worktree("linked")
+        .build()
+        .create_bare_repo("bare.git")
+        .create_repo_with_separate_git_dir("separate", "separate-gitdir")
+        .build();
+
+    let mut config = env.config();
+    let repos = config.scan(&[], ScanMode::Full).repos;
+    let kinds: Vec<(String, RepoKind)> = repos
+        .iter()
+        .map(|repo| {
+            let path = std::path::PathBuf::from(repo.path());
+            let name = path.file_name().unwrap().to_str().unwrap().to_string();
+            (name, repo.kind())
+        })
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            ("bare.git".to_string(), RepoKind::Bare),
+            ("linked".to_string(), RepoKind::Worktree),
+            ("main".to_string(), RepoKind::Normal),
+            ("separate".to_string(), RepoKind::GitFile),
+        ]
+    );
+
+    // The kinds are worked out again when reading the cache.
+    let cached: Vec<RepoKind> =
+        config.get_repos().iter().map(|repo| repo.kind()).collect();
+    assert_eq!(
+        cached,
+        vec![
+            RepoKind::Bare,
+            RepoKind::Worktree,
+            RepoKind::Normal,
+            RepoKind::GitFile
+        ]
+    );
+}
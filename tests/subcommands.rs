@@ -1,14 +1,19 @@
 mod utils;
 
 use std::env;
+use std::fs::remove_dir_all;
 use std::io::Cursor;
 use std::path::PathBuf;
+use std::time::Duration;
 
 use clap::crate_version;
 use regex::{Regex, escape};
 
 use git_global::test_utils::TestEnv;
-use git_global::{Report, subcommands};
+use git_global::{
+    Config, IgnorePattern, PatternStyle, Query, Repo, Report, ScanErrorKind,
+    ScanMode, ScanResult, subcommands,
+};
 
 fn report_to_string(report: &Report) -> String {
     let mut out = Cursor::new(Vec::new());
@@ -19,7 +24,7 @@ fn report_to_string(report: &Report) -> String {
 #[test]
 fn test_info() {
     utils::with_base_dir_of_three_repos(|mut config| {
-        let basedir = config.basedir.clone();
+        let basedir = config.basedirs[0].path.clone();
         let cache = config
             .cache_file
             .clone()
@@ -42,13 +47,16 @@ fn test_info() {
             format!(r"^git-global {}$", crate_version!()),
             format!(r"^============+"),
             format!(r"^Number of repos: 3$"),
-            format!(r"^Base directory: {}$", escape(basedir.to_str().unwrap())),
+            format!(r"^Base directories:$"),
+            format!(r"^  {}$", escape(basedir.to_str().unwrap())),
             format!(r"^Ignored patterns:$"),
             format!(r"^Default command: status$"),
             format!(r"^Verbose: false$"),
             format!(r"^Show untracked: true$"),
             format!(r"^Cache file: {}$", escape(&cache)),
             format!(r"^Cache file age: 0d, 0h, 0m, .s$"),
+            format!(r"^Cache TTL: <none>$"),
+            format!(r"^Cache stale: no$"),
             format!(r"^Manpage file: {}$", escape(&manpage)),
             format!(r"^Detected OS: {}$", escape(env::consts::OS)),
             format!(r"^$"),
@@ -71,7 +79,7 @@ fn test_info() {
 #[test]
 fn test_list() {
     utils::with_base_dir_of_three_repos(|config| {
-        let basedir = config.basedir.clone();
+        let basedir = config.basedirs[0].path.clone();
         let report = subcommands::list::execute(config).unwrap();
         // There are no global messages; the per-repo messages are simply a list
         // of the repo paths themselves.
@@ -90,7 +98,8 @@ fn test_list() {
 #[test]
 fn test_scan() {
     utils::with_base_dir_of_three_repos(|config| {
-        let report = subcommands::scan::execute(config, vec![]).unwrap();
+        let report =
+            subcommands::scan::execute(config, vec![], ScanMode::Full).unwrap();
         // There is one global message about the three repos we found.
         assert_eq!(
             report_to_string(&report),
@@ -99,6 +108,454 @@ fn test_scan() {
     });
 }
 
+#[test]
+fn test_scan_incremental() {
+    let mut env = TestEnv::new();
+    env.create_repo("a").build().create_repo("b").build();
+    subcommands::scan::execute(env.config(), vec![], ScanMode::Full).unwrap();
+
+    env.create_repo("c").build();
+    remove_dir_all(env.tempdir.path().join("a")).unwrap();
+    let report =
+        subcommands::scan::execute(env.config(), vec![], ScanMode::Incremental)
+            .unwrap();
+    let basedir = env.tempdir.path();
+    assert_eq!(
+        report_to_string(&report),
+        format!(
+            "Found 2 repos. Use `git global list` to show them.\n\
+             Added 1 repo:\n  {}\n\
+             Removed 1 repo:\n  {}\n",
+            basedir.join("c").display(),
+            basedir.join("a").display()
+        )
+    );
+}
+
+#[test]
+fn test_stale_cache_is_rescanned() {
+    let mut env = TestEnv::new();
+    env.create_repo("a").build();
+    let mut config = env.config();
+    assert_eq!(config.get_repos().len(), 1);
+    env.create_repo("b").build();
+
+    // Without a TTL, the cache never goes stale.
+    assert_eq!(config.get_repos().len(), 1);
+    assert!(!config.cache_is_stale());
+
+    // Pretend the last scan happened an hour ago.
+    let cache_file = config.cache_file.clone().unwrap();
+    let mut cache: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&cache_file).unwrap())
+            .unwrap();
+    let scanned = cache["scanned"].as_u64().unwrap();
+    cache["scanned"] = (scanned - 60 * 60).into();
+    std::fs::write(&cache_file, cache.to_string()).unwrap();
+    assert!(config.cache_age().unwrap() >= Duration::from_secs(60 * 60));
+
+    config.cache_ttl = Some(Duration::from_secs(60));
+    assert!(config.cache_is_stale());
+    assert_eq!(config.get_repos().len(), 2);
+    assert!(!config.cache_is_stale());
+}
+
+#[test]
+fn test_scan_without_cache_file() {
+    let mut env = TestEnv::new();
+    env.create_repo("a").build().create_repo("b").build();
+    let mut config = env.config();
+    config.cache_file = None;
+
+    // Without anywhere to keep the cache, repos are found afresh each time.
+    let report =
+        subcommands::scan::execute(config.clone(), vec![], ScanMode::Full)
+            .unwrap();
+    assert!(!report.is_failed());
+    assert_eq!(config.get_repos().len(), 2);
+    env.create_repo("c").build();
+    assert_eq!(config.get_repos().len(), 3);
+}
+
+#[test]
+fn test_scan_reports_cache_errors() {
+    let mut env = TestEnv::new();
+    env.create_repo("a").build();
+    let mut config = env.config();
+    // The cache can't be written beneath a regular file.
+    let file = env.tempdir.path().join("file");
+    std::fs::write(&file, "").unwrap();
+    config.cache_file = Some(file.join("repos.json"));
+
+    let report =
+        subcommands::scan::execute(config.clone(), vec![], ScanMode::Full)
+            .unwrap();
+    assert!(report.is_failed());
+    assert!(
+        report_to_string(&report)
+            .contains("Could not save the list of repos: ")
+    );
+    // The repos that were found are still listed.
+    assert_eq!(config.get_repos().len(), 1);
+}
+
+#[test]
+fn test_add_and_forget() {
+    let mut env = TestEnv::new();
+    env.create_repo("a").build().create_repo("b").build();
+    let mut elsewhere = TestEnv::new();
+    elsewhere.create_repo("x").build();
+    let basedir = env.tempdir.path().to_path_buf();
+    let outside = elsewhere.tempdir.path().join("x");
+    let mut config = env.config();
+    let paths = |config: &mut Config| -> Vec<String> {
+        config.get_repos().iter().map(|repo| repo.path()).collect()
+    };
+    let a = basedir.join("a").to_str().unwrap().to_string();
+    let b = basedir.join("b").to_str().unwrap().to_string();
+    let x = outside.to_str().unwrap().to_string();
+
+    // Repos added by hand survive rescans.
+    let report = subcommands::add::execute(
+        config.clone(),
+        vec![outside.clone(), basedir.join("not-a-repo")],
+    )
+    .unwrap();
+    assert!(report.is_failed());
+    let output = report_to_string(&report);
+    assert!(output.starts_with(&format!("Added {}.\n", x)));
+    assert!(output.contains("Could not add"));
+    let mut all = vec![a.clone(), b.clone(), x.clone()];
+    all.sort();
+    assert_eq!(paths(&mut config), all);
+    config.scan(&[], ScanMode::Full);
+    assert_eq!(paths(&mut config), all);
+
+    // Forgotten repos stay forgotten until they're added again.
+    subcommands::forget::execute(config.clone(), vec![basedir.join("a")])
+        .unwrap();
+    subcommands::forget::execute(config.clone(), vec![outside.clone()])
+        .unwrap();
+    assert_eq!(paths(&mut config), vec![b.clone()]);
+    config.scan(&[], ScanMode::Full);
+    assert_eq!(paths(&mut config), vec![b.clone()]);
+    subcommands::add::execute(config.clone(), vec![basedir.join("a")]).unwrap();
+    assert_eq!(paths(&mut config), vec![a, b]);
+
+    // Repos can't be forgotten if the list of repos can't be saved.
+    let file = basedir.join("file");
+    std::fs::write(&file, "").unwrap();
+    config.cache_file = Some(file.join("repos.json"));
+    let report =
+        subcommands::forget::execute(config, vec![basedir.join("b")]).unwrap();
+    assert!(report.is_failed());
+    assert!(report_to_string(&report).starts_with("Could not forget "));
+}
+
+#[test]
+fn test_unusable_cache_is_kept() {
+    let mut env = TestEnv::new();
+    env.create_repo("a").build();
+    let mut config = env.config();
+    let cache_file = config.cache_file.clone().unwrap();
+    let corrupt = "{\"version\": 1, \"repos\": [";
+    std::fs::write(&cache_file, corrupt).unwrap();
+
+    let report =
+        subcommands::scan::execute(config.clone(), vec![], ScanMode::Full)
+            .unwrap();
+    assert!(report.is_failed());
+    assert!(report_to_string(&report).contains(&format!(
+        "Could not save the list of repos: The cache file {} can't be used",
+        cache_file.display()
+    )));
+    assert!(config.add_repo(&env.tempdir.path().join("a")).is_err());
+    assert_eq!(config.get_repos().len(), 1);
+    assert_eq!(std::fs::read_to_string(&cache_file).unwrap(), corrupt);
+}
+
+#[test]
+fn test_concurrent_cache_writers() {
+    let mut env = TestEnv::new();
+    for i in 0..8 {
+        env.create_repo(&format!("repo{}", i)).build();
+    }
+    let config = env.config();
+    let basedir = env.tempdir.path().to_path_buf();
+
+    // Each writer loads, changes and saves the cache, while scans replace
+    // it, and none of them may undo another's change.
+    std::thread::scope(|scope| {
+        for i in 0..8 {
+            let config = config.clone();
+            let path = basedir.join(format!("repo{}", i));
+            scope.spawn(move || config.add_repo(&path).unwrap());
+        }
+        for _ in 0..2 {
+            let mut config = config.clone();
+            scope.spawn(move || {
+                let result = config.scan(&[], ScanMode::Full);
+                assert!(result.cache_error.is_none());
+            });
+        }
+        let config = config.clone();
+        scope.spawn(move || config.forget_repo(&basedir.join("gone")));
+    });
+
+    let cache: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(config.cache_file.unwrap()).unwrap(),
+    )
+    .unwrap();
+    let manual = cache["repos"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|entry| entry["source"] == "manual")
+        .count();
+    assert_eq!(manual, 8);
+    assert_eq!(cache["forgotten"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn test_scan_multiple_basedirs() {
+    let mut env = TestEnv::new();
+    for name in ["one/a", "one/deep/x/y", "two/b", "two/skip", "two/deep/c"] {
+        env.create_repo(name).build();
+    }
+    let tempdir = env.tempdir.path();
+    let gitconfig = tempdir.join(".gitconfig");
+    std::fs::write(
+        &gitconfig,
+        format!(
+            "[global]\n\tbasedir = {one}\n\tbasedir = {two}\n\
+             [global \"{two}\"]\n\tignore = skip\n\tmax-depth = 1\n",
+            one = tempdir.join("one").display(),
+            two = tempdir.join("two").display(),
+        ),
+    )
+    .unwrap();
+    let mut config = Config::from_gitconfig(&gitconfig);
+    config.cache_file = Some(tempdir.join("repos.json"));
+    assert_eq!(config.basedirs.len(), 2);
+    assert_eq!(config.basedirs[1].max_depth, Some(1));
+    assert_eq!(
+        config.basedirs[1].to_string(),
+        format!(
+            "{} (max depth 1; ignoring skip)",
+            tempdir.join("two").display()
+        )
+    );
+
+    // Every base directory is scanned with its own settings, including by the
+    // scan that `get_repos()` runs when there's no cache.
+    let expected: Vec<Repo> = ["one/a", "one/deep/x/y", "two/b"]
+        .iter()
+        .map(|name| Repo::new(tempdir.join(name)))
+        .collect();
+    assert!(config.get_repos() == expected);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_scan_reports_errors() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::symlink;
+
+    let mut env = TestEnv::new();
+    env.create_repo("a").build();
+    let basedir = env.tempdir.path();
+    symlink(basedir.join("nowhere"), basedir.join("dangling")).unwrap();
+    std::fs::create_dir(basedir.join("loop")).unwrap();
+    symlink(basedir, basedir.join("loop/back")).unwrap();
+    std::fs::create_dir(basedir.join(OsStr::from_bytes(b"bad\xff"))).unwrap();
+
+    let mut config = env.config();
+    let report =
+        subcommands::scan::execute(config.clone(), vec![], ScanMode::Full)
+            .unwrap();
+    assert_eq!(
+        report_to_string(&report),
+        "Found 1 repos. Use `git global list` to show them.\n\
+         Could not scan 3 paths (1 broken symbolic link, 1 symbolic link \
+         loop, 1 non-UTF-8 path).\n\
+         Use --verbose to list them.\n"
+    );
+
+    // Verbose output lists each error, and JSON output always does.
+    config.verbose = true;
+    let report =
+        subcommands::scan::execute(config, vec![], ScanMode::Full).unwrap();
+    let output = report_to_string(&report);
+    assert!(output.contains(&format!(
+        "  {}: symbolic link loop back to",
+        basedir.join("loop/back").display()
+    )));
+    let mut out = Cursor::new(Vec::new());
+    report.print_json(&mut out);
+    let json: serde_json::Value =
+        serde_json::from_slice(&out.into_inner()).unwrap();
+    let kinds: Vec<&str> = json["scan_errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|error| error["kind"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        kinds,
+        vec!["non-utf8-path", "broken-symlink", "symlink-loop"]
+    );
+}
+
+#[test]
+fn test_incremental_scan_reports_errors() {
+    use std::ffi::OsStr;
+    use std::fs::File;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::symlink;
+    use std::time::SystemTime;
+
+    let mut env = TestEnv::new();
+    env.create_repo("a").build();
+    let basedir = env.tempdir.path();
+    symlink(basedir.join("nowhere"), basedir.join("dangling")).unwrap();
+    std::fs::create_dir(basedir.join("loop")).unwrap();
+    symlink(basedir, basedir.join("loop/back")).unwrap();
+    std::fs::create_dir(basedir.join(OsStr::from_bytes(b"bad\xff"))).unwrap();
+    let cachedir = TestEnv::new();
+    let mut config = env.config();
+    config.cache_file = Some(cachedir.tempdir.path().join("repos.json"));
+    // The directories have to look old enough to be listed from the
+    // snapshot, which is kept outside of them.
+    let old = SystemTime::now() - Duration::from_secs(60);
+    for dir in [basedir.to_path_buf(), basedir.join("loop")] {
+        File::open(dir).unwrap().set_modified(old).unwrap();
+    }
+
+    let kinds = |result: &ScanResult| -> Vec<ScanErrorKind> {
+        result.errors.iter().map(|error| error.kind).collect()
+    };
+    let full = config.scan(&[], ScanMode::Full);
+    let incremental = config.scan(&[], ScanMode::Incremental);
+    assert!(incremental.unchanged_dirs > 0);
+    assert_eq!(kinds(&incremental), kinds(&full));
+    assert_eq!(
+        kinds(&incremental),
+        vec![
+            ScanErrorKind::NonUtf8Path,
+            ScanErrorKind::BrokenSymlink,
+            ScanErrorKind::SymlinkLoop
+        ]
+    );
+}
+
+#[test]
+fn test_scan_nested() {
+    let mut env = TestEnv::new();
+    env.create_repo("lib").commit("lib.rs", "").build();
+    env.create_repo("outer")
+        .commit("main.rs", "")
+        .submodule("lib", "vendor/lib")
+        .build()
+        .create_repo("outer/inner")
+        .build();
+    let basedir = env.tempdir.path().to_path_buf();
+    let repos_at = |names: &[&str]| -> Vec<Repo> {
+        names
+            .iter()
+            .map(|name| Repo::new(basedir.join(name)))
+            .collect()
+    };
+
+    // By default, the walk stops at the first repo it finds.
+    let mut config = env.config();
+    let repos = config.scan(&[], ScanMode::Full).repos;
+    assert!(repos == repos_at(&["lib", "outer"]));
+
+    // Nested repos can be found, but submodules are left to their
+    // superproject.
+    config.scan_nested = true;
+    let repos = config.scan(&[], ScanMode::Full).repos;
+    assert!(repos == repos_at(&["lib", "outer", "outer/inner"]));
+}
+
+#[test]
+fn test_parallel_scan_matches_walkdir() {
+    let mut env = TestEnv::new();
+    for i in 0..4 {
+        for j in 0..3 {
+            env.create_repo(&format!("d{}/e{}/repo", i, j)).build();
+        }
+        env.create_repo(&format!("d{}/deep/x/y/z/repo", i)).build();
+        env.create_repo(&format!("d{}/node_modules/pkg", i)).build();
+    }
+    env.create_repo("d0/e0/repo/nested").build();
+    env.create_repo("d1/deep/x/inner/repo").build();
+    env.create_repo("skip-me/repo").build();
+    let basedir = env.tempdir.path().to_path_buf();
+    let mut config = env.config();
+    config.scan_nested = true;
+    config.ignored_patterns = ["node_modules", "skip-me"]
+        .iter()
+        .map(|p| IgnorePattern::new(p, PatternStyle::Substring))
+        .collect();
+
+    // How scans used to find repos: one directory at a time, taking the
+    // parent of every `.git` directory.
+    let walkdir_scan = |max_depth: Option<usize>| -> Vec<String> {
+        let mut walker = walkdir::WalkDir::new(&basedir);
+        if let Some(depth) = max_depth {
+            walker = walker.max_depth(depth + 1);
+        }
+        let mut paths: Vec<String> = walker
+            .into_iter()
+            .filter_entry(|e| {
+                let path = e.path().to_str().unwrap();
+                !path.contains("node_modules") && !path.contains("skip-me")
+            })
+            .flatten()
+            .filter(|e| e.file_type().is_dir() && e.file_name() == ".git")
+            .map(|e| e.path().parent().unwrap().to_str().unwrap().to_string())
+            .collect();
+        paths.sort();
+        paths
+    };
+    let parallel_scan = |config: &mut Config| -> Vec<String> {
+        let mut paths: Vec<String> = config
+            .scan(&[], ScanMode::Full)
+            .repos
+            .iter()
+            .map(|repo| repo.path())
+            .collect();
+        paths.sort();
+        paths
+    };
+
+    let expected = walkdir_scan(None);
+    assert_eq!(expected.len(), 18);
+    assert_eq!(parallel_scan(&mut config), expected);
+    for depth in [1, 3, 5] {
+        config.basedirs[0].max_depth = Some(depth);
+        assert_eq!(parallel_scan(&mut config), walkdir_scan(Some(depth)));
+    }
+}
+
+#[test]
+fn test_scan_with_glob_ignore() {
+    let mut env = TestEnv::new();
+    env.create_repo("build/generated")
+        .build()
+        .create_repo("rebuild-tool")
+        .build();
+    let mut config = env.config();
+    config.ignored_patterns =
+        vec![IgnorePattern::new("build", PatternStyle::Glob)];
+
+    let repos = config.scan(&[], ScanMode::Full).repos;
+    let expected = vec![Repo::new(env.tempdir.path().join("rebuild-tool"))];
+    assert!(repos == expected);
+}
+
 #[test]
 fn test_staged() {
     utils::with_base_dir_of_three_repos(|config| {
@@ -149,7 +606,511 @@ fn test_ahead() {
 
     let report = subcommands::ahead::execute(config).unwrap();
     let output = report_to_string(&report);
-    assert!(output.contains(repo_path.to_str().unwrap()));
+    assert_eq!(
+        output,
+        format!(
+            "{}\nmaster...origin/master [ahead 1]\n\n",
+            repo_path.display()
+        )
+    );
+}
+
+#[test]
+fn test_behind() {
+    let mut env = TestEnv::new();
+    env.create_repo("repo-behind")
+        .commit("file.txt", "initial")
+        .setup_remote()
+        .remote_commit("other.txt", "from elsewhere")
+        .build()
+        .create_repo("repo-current")
+        .commit("file.txt", "initial")
+        .setup_remote()
+        .build();
+
+    // Nothing is behind until the new commit is fetched.
+    let report = subcommands::behind::execute(env.config()).unwrap();
+    assert_eq!(report_to_string(&report), "");
+
+    env.create_repo("repo-behind")
+        .fetch()
+        .commit("file.txt", "local")
+        .build();
+    let repo_path = env.tempdir.path().join("repo-behind");
+    let report = subcommands::behind::execute(env.config()).unwrap();
+    assert_eq!(
+        report_to_string(&report),
+        format!(
+            "{}\nmaster...origin/master [ahead 1, behind 1]\n\n",
+            repo_path.display()
+        )
+    );
+}
+
+#[test]
+fn test_fetch() {
+    let mut env = TestEnv::new();
+    env.create_repo("repo-fetch")
+        .commit("file.txt", "initial")
+        .setup_remote()
+        .remote_commit("other.txt", "from elsewhere")
+        .build()
+        .create_repo("repo-unreachable")
+        .commit("file.txt", "initial")
+        .build()
+        .create_repo("repo-broken")
+        .commit("file.txt", "initial");
+    let repo_path = env.tempdir.path().join("repo-fetch");
+    let unreachable_path = env.tempdir.path().join("repo-unreachable");
+    let broken_path = env.tempdir.path().join("repo-broken");
+    git2::Repository::open(&unreachable_path)
+        .unwrap()
+        .remote(
+            "origin",
+            env.tempdir.path().join("gone.git").to_str().unwrap(),
+        )
+        .unwrap();
+
+    let report = subcommands::fetch::execute(env.config(), false).unwrap();
+    assert!(report.is_failed());
+    let output = report_to_string(&report);
+    assert!(output.starts_with("Could not fetch 1 of 2 remotes.\n"));
+    assert!(output.contains(&format!(
+        "{}\norigin: 1 ref updated\n",
+        repo_path.display()
+    )));
+    assert!(output.contains(&format!(
+        "{}\norigin: failed: ",
+        unreachable_path.display()
+    )));
+    let report = subcommands::behind::execute(env.config()).unwrap();
+    assert!(report_to_string(&report).contains("[behind 1]"));
+
+    // Fetching again finds nothing new.
+    let report = subcommands::fetch::execute(env.config(), false).unwrap();
+    assert!(report_to_string(&report).contains("origin: up to date\n"));
+
+    // A repo that's still known, but is no longer a repo, is reported as a
+    // whole.
+    remove_dir_all(broken_path.join(".git")).unwrap();
+    let report = subcommands::fetch::execute(env.config(), false).unwrap();
+    let output = report_to_string(&report);
+    assert!(output.starts_with(
+        "Could not fetch 1 of 2 remotes.\n\
+         Could not read the remotes of 1 repo.\n"
+    ));
+    assert!(output.contains(&format!(
+        "{}\nCould not read remotes: ",
+        broken_path.display()
+    )));
+}
+
+#[test]
+fn test_fetch_prune() {
+    let mut env = TestEnv::new();
+    env.create_repo("repo-prune")
+        .commit("file.txt", "initial")
+        .setup_remote()
+        .build();
+    let remote =
+        git2::Repository::open(env.tempdir.path().join("repo-prune.git"))
+            .unwrap();
+    let head = remote.head().unwrap().peel_to_commit().unwrap();
+    remote.branch("topic", &head, false).unwrap();
+    let repo =
+        git2::Repository::open(env.tempdir.path().join("repo-prune")).unwrap();
+    let has_topic = || repo.find_reference("refs/remotes/origin/topic").is_ok();
+
+    subcommands::fetch::execute(env.config(), false).unwrap();
+    assert!(has_topic());
+    remote
+        .find_branch("topic", git2::BranchType::Local)
+        .unwrap()
+        .delete()
+        .unwrap();
+    subcommands::fetch::execute(env.config(), false).unwrap();
+    assert!(has_topic());
+    subcommands::fetch::execute(env.config(), true).unwrap();
+    assert!(!has_topic());
+}
+
+#[test]
+fn test_pull() {
+    let mut env = TestEnv::new();
+    for name in [
+        "behind", "dirty", "diverged", "detached", "rebasing", "blocked",
+    ] {
+        env.create_repo(name)
+            .commit("file.txt", "initial")
+            .setup_remote()
+            .remote_commit("other.txt", "from elsewhere")
+            .fetch();
+    }
+    env.create_repo("dirty").unstaged("file.txt", "changed");
+    env.create_repo("diverged").commit("file.txt", "local");
+    env.create_repo("current")
+        .commit("file.txt", "initial")
+        .setup_remote();
+    let path = |name: &str| env.tempdir.path().join(name);
+    let detached = git2::Repository::open(path("detached")).unwrap();
+    detached
+        .set_head_detached(detached.head().unwrap().target().unwrap())
+        .unwrap();
+    std::fs::create_dir(path("rebasing/.git/rebase-merge")).unwrap();
+    // An untracked file is in the way of the upstream's new one.
+    std::fs::write(path("blocked/other.txt"), "in the way").unwrap();
+    let head = |name: &str| {
+        let repo = git2::Repository::open(path(name)).unwrap();
+        repo.head().unwrap().target().unwrap()
+    };
+    let before = head("behind");
+    let blocked_before = head("blocked");
+
+    let expected = |verb: &str| {
+        vec![
+            format!(
+                "{}\n{} master by 1 commit to origin/master ",
+                path("behind").display(),
+                verb
+            ),
+            format!(
+                "{}\nSkipped: the working tree has uncommitted changes.\n",
+                path("dirty").display()
+            ),
+            format!(
+                "{}\nSkipped: master has diverged from origin/master \
+                 [ahead 1, behind 1].\n",
+                path("diverged").display()
+            ),
+            format!(
+                "{}\nSkipped: HEAD is detached.\n",
+                path("detached").display()
+            ),
+            format!(
+                "{}\nSkipped: a rebase is in progress.\n",
+                path("rebasing").display()
+            ),
+        ]
+    };
+
+    let report = subcommands::pull::execute(env.config(), true).unwrap();
+    assert!(!report.is_failed());
+    let output = report_to_string(&report);
+    for part in expected("Would fast-forward") {
+        assert!(output.contains(&part), "{} not in {}", part, output);
+    }
+    assert!(!output.contains(path("current").to_str().unwrap()));
+    assert_eq!(head("behind"), before);
+
+    let report = subcommands::pull::execute(env.config(), false).unwrap();
+    assert!(report.is_failed());
+    let output = report_to_string(&report);
+    for part in expected("Fast-forwarded") {
+        assert!(output.contains(&part), "{} not in {}", part, output);
+    }
+    // Neither the working tree nor the branch moved where the checkout
+    // failed.
+    assert!(output.contains(&format!(
+        "{}\nCould not fast-forward: ",
+        path("blocked").display()
+    )));
+    assert_eq!(head("blocked"), blocked_before);
+    assert_eq!(
+        std::fs::read_to_string(path("blocked/other.txt")).unwrap(),
+        "in the way"
+    );
+    let behind = git2::Repository::open(path("behind")).unwrap();
+    let upstream = behind
+        .find_reference("refs/remotes/origin/master")
+        .unwrap()
+        .target()
+        .unwrap();
+    assert_eq!(head("behind"), upstream);
+    assert!(path("behind/other.txt").exists());
+    assert!(behind.statuses(None).unwrap().is_empty());
+
+    // Now that it's up to date, there's nothing to do.
+    let report = subcommands::pull::execute(env.config(), false).unwrap();
+    assert!(!report_to_string(&report).contains("Fast-forwarded"));
+}
+
+#[test]
+fn test_push() {
+    let mut env = TestEnv::new();
+    env.create_repo("ahead")
+        .commit("file.txt", "initial")
+        .setup_remote()
+        .commit("file.txt", "local")
+        .commit("file.txt", "more local")
+        .build()
+        .create_repo("diverged")
+        .commit("file.txt", "initial")
+        .setup_remote()
+        .remote_commit("other.txt", "from elsewhere")
+        .fetch()
+        .commit("file.txt", "local")
+        .build()
+        // The remote moved since the last fetch, so pushing isn't a
+        // fast-forward after all.
+        .create_repo("stale")
+        .commit("file.txt", "initial")
+        .setup_remote()
+        .remote_commit("other.txt", "from elsewhere")
+        .commit("file.txt", "local");
+    let path = |name: &str| env.tempdir.path().join(name);
+    let head = |name: &str, reference: &str| {
+        let repo = git2::Repository::open(path(name)).unwrap();
+        repo.find_reference(reference).unwrap().target().unwrap()
+    };
+    let remote_before = head("ahead.git", "refs/heads/master");
+
+    let report = subcommands::push::execute(env.config(), true).unwrap();
+    assert!(!report.is_failed());
+    let output = report_to_string(&report);
+    assert!(output.contains(&format!(
+        "{}\nWould push master to origin/master (2 commits).\n",
+        path("ahead").display()
+    )));
+    assert_eq!(head("ahead.git", "refs/heads/master"), remote_before);
+
+    let report = subcommands::push::execute(env.config(), false).unwrap();
+    assert!(report.is_failed());
+    let output = report_to_string(&report);
+    assert!(output.contains(&format!(
+        "{}\nPushed master to origin/master (2 commits).\n",
+        path("ahead").display()
+    )));
+    assert!(output.contains(&format!(
+        "{}\nSkipped: master has diverged from origin/master \
+         [ahead 1, behind 1].\n",
+        path("diverged").display()
+    )));
+    assert!(output.contains(&format!(
+        "{}\nCould not push master to origin/master: ",
+        path("stale").display()
+    )));
+    let local = head("ahead", "refs/heads/master");
+    assert_eq!(head("ahead.git", "refs/heads/master"), local);
+    assert_eq!(head("ahead", "refs/remotes/origin/master"), local);
+
+    // Nothing is ahead of the remote anymore.
+    let report = subcommands::push::execute(env.config(), false).unwrap();
+    let output = report_to_string(&report);
+    assert!(!output.contains("Pushed"), "{}", output);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_exec() {
+    use subcommands::exec::ExecOptions;
+
+    let mut env = TestEnv::new();
+    env.create_repo("a")
+        .commit("file.txt", "content")
+        .build()
+        .create_repo("b")
+        .commit("other.txt", "content");
+    let sh = |script: &str| -> Vec<String> {
+        vec!["sh".to_string(), "-c".to_string(), script.to_string()]
+    };
+    let path = |name: &str| env.tempdir.path().join(name);
+
+    let report = subcommands::exec::execute(
+        env.config(),
+        sh("echo \"$GIT_GLOBAL_REPO_NAME $GIT_GLOBAL_REPO\"; ls"),
+        ExecOptions::default(),
+    )
+    .unwrap();
+    assert!(!report.is_failed());
+    assert_eq!(
+        report_to_string(&report),
+        format!(
+            "{a}\na {a}\nfile.txt\n\n{b}\nb {b}\nother.txt\n\n",
+            a = path("a").display(),
+            b = path("b").display()
+        )
+    );
+
+    let script = sh("test -e file.txt || { echo missing >&2; exit 3; }");
+    let report = subcommands::exec::execute(
+        env.config(),
+        script.clone(),
+        ExecOptions::default(),
+    )
+    .unwrap();
+    assert!(report.is_failed());
+    let only_failed = ExecOptions {
+        only_failed: true,
+        ..Default::default()
+    };
+    let report =
+        subcommands::exec::execute(env.config(), script, only_failed).unwrap();
+    assert_eq!(
+        report_to_string(&report),
+        format!(
+            "The command failed in 1 of 2 repos.\n{}\nmissing\n\
+             Exited with status 3.\n\n",
+            path("b").display()
+        )
+    );
+
+    // With one job at a time, nothing runs after the first failure.
+    let fail_fast = ExecOptions {
+        jobs: 1,
+        fail_fast: true,
+        ..Default::default()
+    };
+    let report =
+        subcommands::exec::execute(env.config(), sh("exit 1"), fail_fast)
+            .unwrap();
+    let output = report_to_string(&report);
+    assert!(output.starts_with(
+        "The command failed in 1 of 2 repos.\n\
+         Skipped 1 repo after the command failed.\n"
+    ));
+}
+
+#[test]
+fn test_git() {
+    let mut env = TestEnv::new();
+    env.create_repo("a")
+        .commit("file.txt", "content")
+        .build()
+        .create_repo("b")
+        .commit("file.txt", "content");
+    let args = ["rev-parse", "--abbrev-ref", "HEAD"].map(String::from);
+
+    let report =
+        subcommands::git::execute(env.config(), args.to_vec()).unwrap();
+    assert!(!report.is_failed());
+    assert_eq!(
+        report_to_string(&report),
+        format!(
+            "{}\nmaster\n\n{}\nmaster\n\n",
+            env.tempdir.path().join("a").display(),
+            env.tempdir.path().join("b").display()
+        )
+    );
+
+    let args = vec!["no-such-command".to_string()];
+    let report = subcommands::git::execute(env.config(), args).unwrap();
+    assert!(report.is_failed());
+}
+
+#[test]
+fn test_grep() {
+    use subcommands::grep::{GrepOptions, Source};
+
+    let mut env = TestEnv::new();
+    env.create_repo("a")
+        .commit("notes.txt", "hello world\nfoo\nHello again\n")
+        .commit("src.rs", "// hello\n")
+        .stage("notes.txt", "hello world\nhello, index\n")
+        .unstaged("notes.txt", "hello, working tree\n")
+        .build()
+        .create_repo("b")
+        .commit("binary.bin", "hello\0world")
+        .commit("other.txt", "nothing\r\nto see, hello\r\n");
+    let a = env.tempdir.path().join("a");
+    let b = env.tempdir.path().join("b");
+    let grep = |pattern: &str, options: GrepOptions| {
+        let report =
+            subcommands::grep::execute(env.config(), pattern, options).unwrap();
+        report_to_string(&report)
+    };
+
+    assert_eq!(
+        grep("hello", GrepOptions::default()),
+        format!(
+            "{a}:notes.txt:1:hello world\n{a}:src.rs:1:// hello\n\
+             {b}:other.txt:2:to see, hello\n",
+            a = a.display(),
+            b = b.display()
+        )
+    );
+    let ignore_case = GrepOptions {
+        ignore_case: true,
+        pathspecs: vec!["*.txt".to_string()],
+        ..Default::default()
+    };
+    assert_eq!(
+        grep("^hello", ignore_case),
+        format!(
+            "{a}:notes.txt:1:hello world\n{a}:notes.txt:3:Hello again\n",
+            a = a.display()
+        )
+    );
+    let index = GrepOptions {
+        source: Source::Index,
+        ..Default::default()
+    };
+    assert!(
+        grep("hello, ", index).starts_with(&format!(
+            "{}:notes.txt:2:hello, index\n",
+            a.display()
+        ))
+    );
+    let worktree = GrepOptions {
+        source: Source::WorkTree,
+        ..Default::default()
+    };
+    assert!(grep("hello, ", worktree).starts_with(&format!(
+        "{}:notes.txt:1:hello, working tree\n",
+        a.display()
+    )));
+
+    let report = subcommands::grep::execute(
+        env.config(),
+        "no such text",
+        GrepOptions::default(),
+    )
+    .unwrap();
+    assert!(report.is_failed());
+
+    let report = subcommands::grep::execute(
+        env.config(),
+        "hello, working",
+        GrepOptions {
+            source: Source::WorkTree,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let mut out = Cursor::new(Vec::new());
+    report.print_json(&mut out);
+    let json: serde_json::Value =
+        serde_json::from_slice(&out.into_inner()).unwrap();
+    assert_eq!(
+        json["repo_messages"][a.to_str().unwrap()],
+        serde_json::json!([format!(
+            "{}:notes.txt:1:hello, working tree",
+            a.display()
+        )])
+    );
+
+    let error =
+        subcommands::grep::execute(env.config(), "(", GrepOptions::default())
+            .err()
+            .unwrap();
+    assert!(error.to_string().starts_with("Invalid pattern: "));
+
+    // A repo that can't be searched fails the search, even with matches.
+    env.create_repo("c").commit("file.txt", "hello\n").build();
+    let c = env.tempdir.path().join("c");
+    std::fs::write(
+        c.join(".git/refs/heads/master"),
+        format!("{}\n", "1".repeat(40)),
+    )
+    .unwrap();
+    let mut config = env.config();
+    config.scan(&[], ScanMode::Full);
+    let report =
+        subcommands::grep::execute(config, "hello", GrepOptions::default())
+            .unwrap();
+    assert!(report.is_failed());
+    let output = report_to_string(&report);
+    assert!(output.starts_with(&format!("Could not search {}: ", c.display())));
+    assert!(output.contains(&format!("{}:notes.txt:1:", a.display())));
 }
 
 #[test]
@@ -175,12 +1136,354 @@ fn test_ignore_and_ignored() {
     let config = git_global::Config::from_gitconfig(&gitconfig_path);
 
     // Verify isolation: basedir should come from the gitconfig we wrote.
-    assert_eq!(config.basedir, env.tempdir.path());
+    assert_eq!(config.basedirs.len(), 1);
+    assert_eq!(config.basedirs[0].path, env.tempdir.path());
 
-    subcommands::ignore::execute(config, "my-pattern").unwrap();
+    // New patterns are globs, even though unprefixed ones aren't.
+    let report = subcommands::ignore::execute(config, "my-pattern").unwrap();
+    assert!(report_to_string(&report).starts_with(
+        "Added 'glob:my-pattern' to global.ignore as a glob pattern."
+    ));
 
     let config = git_global::Config::from_gitconfig(&gitconfig_path);
     let report = subcommands::ignored::execute(config).unwrap();
     let output = report_to_string(&report);
-    assert!(output.contains("my-pattern"));
+    assert!(output.contains("glob:my-pattern (glob)"));
+}
+
+#[test]
+fn test_unprefixed_ignore_patterns_match_substrings() {
+    let mut env = TestEnv::new();
+    env.create_repo("old/project")
+        .build()
+        .create_repo("src/node_modules_cache/pkg")
+        .build()
+        .create_repo("build/generated")
+        .build()
+        .create_repo("kept")
+        .build();
+    let basedir = env.tempdir.path();
+    let gitconfig = env.write_gitconfig();
+    std::fs::write(
+        &gitconfig,
+        format!(
+            "[global]\n\tbasedir = {}\n\tignore = {},node_modules,glob:/build\n",
+            basedir.display(),
+            basedir.join("old").display(),
+        ),
+    )
+    .unwrap();
+    let mut config = Config::from_gitconfig(&gitconfig);
+    config.cache_file = Some(basedir.join("repos.json"));
+    assert_eq!(config.ignore_style, PatternStyle::Substring);
+
+    // Patterns written before globs existed still ignore the same paths.
+    let repos = config.scan(&[], ScanMode::Full).repos;
+    assert!(repos == vec![Repo::new(basedir.join("kept"))]);
+
+    // Unless unprefixed patterns are made globs, which only match names.
+    config.ignore_style = PatternStyle::Glob;
+    config.ignored_patterns = vec![
+        IgnorePattern::new("node_modules", config.ignore_style),
+        IgnorePattern::new("glob:/build", config.ignore_style),
+    ];
+    let repos = config.scan(&[], ScanMode::Full).repos;
+    assert_eq!(repos.len(), 3);
+}
+
+#[test]
+fn test_invalid_repos_are_reported() {
+    let mut env = TestEnv::new();
+    env.create_repo("a")
+        .commit("file.txt", "content")
+        .build()
+        .create_repo("gone")
+        .build();
+    let mut config = env.config();
+    assert_eq!(config.get_repos().len(), 2);
+    remove_dir_all(env.tempdir.path().join("gone/.git")).unwrap();
+    let gone = env.tempdir.path().join("gone");
+
+    let reports = [
+        subcommands::status::execute(env.config()).unwrap(),
+        subcommands::staged::execute(env.config()).unwrap(),
+        subcommands::unstaged::execute(env.config()).unwrap(),
+        subcommands::stashed::execute(env.config()).unwrap(),
+        subcommands::ahead::execute(env.config()).unwrap(),
+        subcommands::behind::execute(env.config()).unwrap(),
+        subcommands::push::execute(env.config(), true).unwrap(),
+    ];
+    for report in reports {
+        assert!(report.is_failed());
+        assert!(report_to_string(&report).starts_with(&format!(
+            "{}\nCould not read repo: ",
+            gone.display()
+        )));
+    }
+}
+
+#[test]
+fn test_prune() {
+    let mut env = TestEnv::new();
+    env.create_repo("a")
+        .build()
+        .create_repo("b")
+        .build()
+        .create_repo("c")
+        .build();
+    let basedir = env.tempdir.path().to_path_buf();
+    let mut config = env.config();
+    assert_eq!(config.get_repos().len(), 3);
+    remove_dir_all(basedir.join("a")).unwrap();
+    remove_dir_all(basedir.join("b/.git")).unwrap();
+    let expected = |verb: &str| {
+        format!(
+            "{} 2 repos:\n  {} (missing)\n  {} (not a git repo)\n",
+            verb,
+            basedir.join("a").display(),
+            basedir.join("b").display()
+        )
+    };
+
+    let report = subcommands::prune::execute(env.config(), true).unwrap();
+    assert_eq!(report_to_string(&report), expected("Would prune"));
+    assert_eq!(config.get_repos().len(), 2);
+
+    let report = subcommands::prune::execute(env.config(), false).unwrap();
+    assert_eq!(report_to_string(&report), expected("Pruned"));
+    let repos = config.get_repos();
+    assert_eq!(repos.len(), 1);
+    assert_eq!(repos[0].path(), basedir.join("c").to_str().unwrap());
+
+    let report = subcommands::prune::execute(env.config(), false).unwrap();
+    assert!(!report.is_failed());
+    assert_eq!(report_to_string(&report), "Nothing to prune.\n");
+
+    // A cache that can't be locked can't be pruned.
+    let file = basedir.join("file");
+    std::fs::write(&file, "").unwrap();
+    config.cache_file = Some(file.join("repos.json"));
+    let report = subcommands::prune::execute(config, false).unwrap();
+    assert!(report.is_failed());
+    assert!(report_to_string(&report).starts_with("Could not prune: "));
+}
+
+#[test]
+fn test_tags() {
+    let mut env = TestEnv::new();
+    env.create_repo("a")
+        .build()
+        .create_repo("b")
+        .build()
+        .create_repo("client-x")
+        .build();
+    let basedir = env.tempdir.path().to_path_buf();
+    let mut config = env.config();
+    assert_eq!(config.get_repos().len(), 3);
+    let a = basedir.join("a").to_str().unwrap().to_string();
+    let x = basedir.join("client-x").to_str().unwrap().to_string();
+    let paths = |config: &mut Config| -> Vec<String> {
+        config.get_repos().iter().map(|repo| repo.path()).collect()
+    };
+
+    let targets = vec![a.clone(), "client-*".to_string(), "nope".to_string()];
+    let report =
+        subcommands::tag::execute(env.config(), Some("work".into()), targets)
+            .unwrap();
+    assert_eq!(
+        report_to_string(&report),
+        format!(
+            "Tagged {} with work.\nTagged {} with work.\nNo known repos \
+             match nope.\n",
+            a, x
+        )
+    );
+    let report = subcommands::tag::execute(env.config(), None, vec![]).unwrap();
+    assert_eq!(
+        report_to_string(&report),
+        format!("work (2 repos):\n  {}\n  {}\n", a, x)
+    );
+
+    // Only tagged repos are listed, for any of the given tags.
+    config.tags = vec!["work".to_string()];
+    assert_eq!(paths(&mut config), vec![a.clone(), x.clone()]);
+    config.tags = vec!["missing".to_string(), "work".to_string()];
+    assert_eq!(paths(&mut config), vec![a.clone(), x.clone()]);
+
+    // Tags survive rescans.
+    config.scan(&[], ScanMode::Full);
+    assert_eq!(paths(&mut config).len(), 2);
+
+    let report =
+        subcommands::untag::execute(env.config(), "work", vec![a.clone()])
+            .unwrap();
+    assert_eq!(
+        report_to_string(&report),
+        format!("Removed work from {}.\n", a)
+    );
+    assert_eq!(paths(&mut config), vec![x.clone()]);
+
+    let report =
+        subcommands::untag::execute(env.config(), "work", vec![]).unwrap();
+    assert_eq!(
+        report_to_string(&report),
+        format!("Removed work from {}.\n", x)
+    );
+    assert!(paths(&mut config).is_empty());
+    config.tags.clear();
+    assert_eq!(paths(&mut config).len(), 3);
+
+    let report =
+        subcommands::tag::execute(env.config(), Some(" ".into()), vec![a])
+            .unwrap();
+    assert!(report.is_failed());
+    assert_eq!(
+        report_to_string(&report),
+        "Could not tag repos: Tags can't be empty\n"
+    );
+    let file = basedir.join("file");
+    std::fs::write(&file, "").unwrap();
+    config.cache_file = Some(file.join("repos.json"));
+    let report = subcommands::untag::execute(config, "work", vec![]).unwrap();
+    assert!(report.is_failed());
+    assert!(report_to_string(&report).starts_with("Could not untag repos: "));
+}
+
+#[test]
+fn test_tag_populates_the_cache() {
+    let mut env = TestEnv::new();
+    env.create_repo("a").build().create_repo("b").build();
+    let a = env.tempdir.path().join("a").to_str().unwrap().to_string();
+
+    // The cache is populated without selecting repos, so repos outside of
+    // the selection can be tagged, too.
+    let mut config = env.config();
+    config.filters = vec!["b".to_string()];
+    let report =
+        subcommands::tag::execute(config, Some("work".into()), vec![a.clone()])
+            .unwrap();
+    assert!(!report.is_failed());
+    assert_eq!(
+        report_to_string(&report),
+        format!("Tagged {} with work.\n", a)
+    );
+    assert_eq!(env.config().get_repos().len(), 2);
+}
+
+#[test]
+fn test_filter_and_exclude() {
+    let mut env = TestEnv::new();
+    env.create_repo("work/api")
+        .build()
+        .create_repo("work/client")
+        .build()
+        .create_repo("home/api")
+        .build();
+    let basedir = env.tempdir.path().to_path_buf();
+    let mut config = env.config();
+    let path = |name: &str| basedir.join(name).to_str().unwrap().to_string();
+    let paths = |config: &mut Config| -> Vec<String> {
+        config.get_repos().iter().map(|repo| repo.path()).collect()
+    };
+
+    // Patterns without a `/` match directory names, ...
+    config.filters = vec!["api".to_string()];
+    assert_eq!(paths(&mut config), vec![path("home/api"), path("work/api")]);
+
+    // ... while those with one match full paths.
+    config.filters = vec![format!("{}/work/*", basedir.display())];
+    assert_eq!(
+        paths(&mut config),
+        vec![path("work/api"), path("work/client")]
+    );
+    config.excludes = vec!["c*".to_string()];
+    assert_eq!(paths(&mut config), vec![path("work/api")]);
+
+    config.filters.clear();
+    assert_eq!(paths(&mut config), vec![path("home/api"), path("work/api")]);
+}
+
+#[test]
+fn test_where() {
+    let mut env = TestEnv::new();
+    env.create_repo("dirty")
+        .commit("file.txt", "content")
+        .unstaged("file.txt", "changed")
+        .build()
+        .create_repo("ahead")
+        .commit("file.txt", "content")
+        .setup_remote()
+        .commit("file.txt", "new content")
+        .build()
+        .create_repo("stashed")
+        .commit("file.txt", "content")
+        .unstaged("file.txt", "changed")
+        .stash("stash")
+        .build();
+    let mut config = env.config();
+    let mut names = |query: &str| -> Vec<String> {
+        config.query = Some(Query::parse(query).unwrap());
+        config
+            .get_repos()
+            .iter()
+            .map(|repo| repo.path().rsplit('/').next().unwrap().to_string())
+            .collect()
+    };
+    assert_eq!(names("dirty && !has_remote"), vec!["dirty"]);
+    assert_eq!(names("ahead > 0 || stashes > 0"), vec!["ahead", "stashed"]);
+    assert_eq!(names("remote ~ '*.git' && behind == 0"), vec!["ahead"]);
+    assert!(names("age > 1d").is_empty());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_watch() {
+    use std::time::Instant;
+
+    use git_global::subcommands::watch::{self, Change, Watcher};
+
+    let mut env = TestEnv::new();
+    env.create_repo("a").build();
+    let basedir = env.tempdir.path().to_path_buf();
+    let mut config = env.config();
+    let mut watcher = Watcher::new(env.config()).unwrap();
+    assert!(watcher.start().unwrap().is_empty());
+    let wait_for = |watcher: &mut Watcher, expected: Change| {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while Instant::now() < deadline {
+            let changes = watcher.wait(Duration::from_millis(100)).unwrap();
+            if changes.contains(&expected) {
+                return;
+            }
+        }
+        panic!("timed out waiting for: {}", expected);
+    };
+
+    // Repos are noticed wherever they appear, ...
+    env.create_repo("dir/b").build();
+    let b = Repo::new(basedir.join("dir/b"));
+    wait_for(&mut watcher, Change::Added(b.clone()));
+    let paths: Vec<String> =
+        config.get_repos().iter().map(|repo| repo.path()).collect();
+    assert!(paths.contains(&b.path()));
+
+    // ... and forgotten once they're gone.
+    remove_dir_all(basedir.join("a")).unwrap();
+    wait_for(&mut watcher, Change::Removed(Repo::new(basedir.join("a"))));
+    assert_eq!(config.get_repos().len(), 1);
+
+    // Changes are reported like any other results.
+    let report = watch::report(&[Change::Added(b.clone())]);
+    assert_eq!(report_to_string(&report), format!("Added {}.\n", b));
+    let mut out = Cursor::new(Vec::new());
+    report.write(&mut out, true);
+    let json: serde_json::Value =
+        serde_json::from_slice(&out.into_inner()).unwrap();
+    assert_eq!(json["added"], serde_json::json!([b.path()]));
+
+    // Failing to watch is an error.
+    let mut config = env.config();
+    config.cache_file = None;
+    let error = watch::execute(config).err().unwrap();
+    assert!(error.to_string().starts_with("Could not watch for repos: "));
 }